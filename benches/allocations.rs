@@ -0,0 +1,72 @@
+//! Counts the allocations of `Memory::learn`. Words are interned, so learning a line only allocates for words and word combinations the chain hasn't seen before, and learning the same lines again allocates far less than learning them the first time.
+
+mod common;
+
+use markov::Memory;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// The system allocator, counting every allocation
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Learn every line of the corpus, and print how much that allocated
+fn learn(memory: &mut Memory, corpus: &[String], name: &str) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for line in corpus {
+        memory.learn(line);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+    println!(
+        "{}: {} allocations ({:.2} per line), {} bytes, in {:?}",
+        name,
+        allocations,
+        allocations as f64 / corpus.len() as f64,
+        bytes,
+        elapsed
+    );
+}
+
+fn main() {
+    let lines = common::line_count(200_000);
+    let corpus = common::corpus(lines);
+    let words: usize = corpus.iter().map(|line| line.split(' ').count()).sum();
+    println!("Learning {} lines with {} words", lines, words);
+
+    let mut memory = Memory::default();
+    learn(&mut memory, &corpus, "first pass");
+    println!(
+        "The chain knows {} words and {} contexts",
+        memory.vocabulary(),
+        memory.state_count()
+    );
+    learn(&mut memory, &corpus, "second pass, every word is known");
+}
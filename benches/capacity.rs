@@ -0,0 +1,37 @@
+//! Compares learning a corpus into an empty chain with learning it into a chain that was created with `Memory::with_capacity`, which doesn't have to grow its map of contexts over and over.
+
+mod common;
+
+use markov::Memory;
+
+fn main() {
+    let lines = common::line_count(200_000);
+    let corpus = common::corpus(lines);
+    let learn = |mut memory: Memory| {
+        for line in &corpus {
+            memory.learn(line);
+        }
+        memory
+    };
+
+    let (grown, grown_time) = common::fastest(3, || learn(Memory::default()));
+    let states = grown.state_count();
+    println!(
+        "Learning {} lines with {} contexts into an empty chain: {:?} ({:.0} lines/s)",
+        lines,
+        states,
+        grown_time,
+        common::lines_per_second(lines, grown_time)
+    );
+    drop(grown);
+
+    let (reserved, reserved_time) = common::fastest(3, || learn(Memory::with_capacity(states)));
+    println!(
+        "Learning them into a chain with room for {} contexts: {:?} ({:.0} lines/s), {:.1}% less time",
+        states,
+        reserved_time,
+        common::lines_per_second(lines, reserved_time),
+        100.0 * (1.0 - reserved_time.as_secs_f64() / grown_time.as_secs_f64())
+    );
+    assert_eq!(reserved.state_count(), states);
+}
@@ -0,0 +1,58 @@
+//! Helpers shared by the benchmarks. The benchmarks don't use a harness, run them with `cargo bench --bench <name>`, and pass the amount of lines to learn as an argument, e.g. `cargo bench --bench learn_par -- 1000000`.
+
+// Not every benchmark uses every helper
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+
+/// The amount of lines to learn, from the first argument that is a number
+pub fn line_count(default: usize) -> usize {
+    std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Generate `lines` lines of text, the same ones on every run. Some words are much more common than others, like in real text, so the chain has many rare and a few very common word combinations.
+pub fn corpus(lines: usize) -> Vec<String> {
+    // xorshift, so every run learns the same lines without depending on the features of rand
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..lines)
+        .map(|_| {
+            let words = 3 + next() % 12;
+            (0..words)
+                .map(|_| {
+                    // Squaring a uniform number between 0 and 1 makes small word numbers more likely
+                    let uniform = (next() % 10_000) as f64 / 10_000.0;
+                    format!("word{}", (uniform * uniform * 20_000.0) as usize)
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Run `f` `runs` times, and return the result and the run time of the fastest run
+pub fn fastest<T>(runs: usize, mut f: impl FnMut() -> T) -> (T, Duration) {
+    let mut best: Option<(T, Duration)> = None;
+    for _ in 0..runs {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        if best.as_ref().is_none_or(|(_, fastest)| elapsed < *fastest) {
+            best = Some((result, elapsed));
+        }
+    }
+    best.expect("A benchmark must run at least once")
+}
+
+/// The amount of lines that were learned per second
+pub fn lines_per_second(lines: usize, elapsed: Duration) -> f64 {
+    lines as f64 / elapsed.as_secs_f64()
+}
@@ -0,0 +1,38 @@
+//! Compares the size of saved chains, and how long saving and loading them takes, for every compression, see `Compression`.
+
+mod common;
+
+use markov::{Compression, Memory};
+use std::time::Instant;
+
+fn main() {
+    let lines = common::line_count(200_000);
+    let mut memory = Memory::default();
+    for line in common::corpus(lines) {
+        memory.learn(&line);
+    }
+    println!("Saving a chain of {} lines", lines);
+
+    let compressions = [
+        ("stored", Compression::Stored),
+        ("deflate", Compression::default()),
+        ("deflate level 9", Compression::Deflated { level: Some(9) }),
+        ("zstd", Compression::Zstd { level: None }),
+        ("zstd level 22", Compression::Zstd { level: Some(22) }),
+    ];
+    for (name, compression) in compressions.iter() {
+        let path = std::env::temp_dir().join(format!("markov-bench-{}.zip", std::process::id()));
+        let (_, save_time) =
+            common::fastest(3, || memory.save_with_options(&path, *compression).unwrap());
+        let size = std::fs::metadata(&path).unwrap().len();
+        let start = Instant::now();
+        let loaded = Memory::load(&path).unwrap();
+        let load_time = start.elapsed();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.transition_count(), memory.transition_count());
+        println!(
+            "{}: {} bytes, saved in {:?}, loaded in {:?}",
+            name, size, save_time, load_time
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Measures how fast chains learn and speak with the hasher of their maps. Run it once as it is, with SipHash, and once with `--features fast-hash`, with FxHash, to compare them. With the `fast-hash` feature, it also compares the hashers directly on keys like the contexts of a chain.
+
+mod common;
+
+use markov::Memory;
+use std::time::Instant;
+
+fn main() {
+    let lines = common::line_count(200_000);
+    let corpus = common::corpus(lines);
+    let hasher = if cfg!(feature = "fast-hash") {
+        "FxHash"
+    } else {
+        "SipHash"
+    };
+    println!("Hashing with {}", hasher);
+
+    let (memory, learn_time) = common::fastest(3, || {
+        let mut memory = Memory::default();
+        for line in &corpus {
+            memory.learn(line);
+        }
+        memory
+    });
+    println!(
+        "learn: {} lines in {:?} ({:.0} lines/s)",
+        lines,
+        learn_time,
+        common::lines_per_second(lines, learn_time)
+    );
+
+    let starts: Vec<_> = corpus
+        .iter()
+        .take(10_000)
+        .filter_map(|line| line.split(' ').next())
+        .collect();
+    let mut rng = rand::thread_rng();
+    let start = Instant::now();
+    let words: usize = starts
+        .iter()
+        .filter_map(|word| memory.speak_with_rng(word, &mut rng))
+        .map(|sentence| sentence.split(' ').count())
+        .sum();
+    let elapsed = start.elapsed();
+    println!(
+        "speak: {} sentences with {} words in {:?} ({:.0} words/s)",
+        starts.len(),
+        words,
+        elapsed,
+        words as f64 / elapsed.as_secs_f64()
+    );
+
+    #[cfg(feature = "fast-hash")]
+    compare_hashers();
+}
+
+/// Count every context of the corpus in a map with either hasher
+#[cfg(feature = "fast-hash")]
+fn compare_hashers() {
+    use std::collections::hash_map::RandomState;
+    use std::collections::HashMap;
+    use std::hash::BuildHasher;
+
+    fn count<H: BuildHasher + Default>(keys: &[[u32; 3]]) -> usize {
+        let mut map = HashMap::<_, usize, H>::default();
+        for key in keys {
+            *map.entry(*key).or_insert(0) += 1;
+        }
+        keys.iter().filter(|key| map[*key] > 1).count()
+    }
+
+    let mut state = 1u32;
+    let keys: Vec<[u32; 3]> = (0..2_000_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            [state % 20_000, (state >> 8) % 20_000, state >> 28]
+        })
+        .collect();
+    let (sip, sip_time) = common::fastest(3, || count::<RandomState>(&keys));
+    let (fx, fx_time) = common::fastest(3, || {
+        count::<std::hash::BuildHasherDefault<rustc_hash::FxHasher>>(&keys)
+    });
+    assert_eq!(sip, fx);
+    println!(
+        "Counting {} contexts: SipHash {:?}, FxHash {:?}, {:.2}x faster",
+        keys.len(),
+        sip_time,
+        fx_time,
+        sip_time.as_secs_f64() / fx_time.as_secs_f64()
+    );
+}
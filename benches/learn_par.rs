@@ -0,0 +1,39 @@
+//! Compares learning a corpus on a single thread with `Memory::learn_par`, which should get close to a linear speedup with the amount of threads.
+
+mod common;
+
+use markov::Memory;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+fn main() {
+    let lines = common::line_count(200_000);
+    let corpus = common::corpus(lines);
+    println!("Learning {} lines", lines);
+
+    let (sequential, sequential_time) = common::fastest(3, || {
+        let mut memory = Memory::default();
+        for line in &corpus {
+            memory.learn(line);
+        }
+        memory
+    });
+    println!(
+        "learn: {:?} ({:.0} lines/s)",
+        sequential_time,
+        common::lines_per_second(lines, sequential_time)
+    );
+
+    let threads = rayon::current_num_threads();
+    let (parallel, parallel_time) =
+        common::fastest(3, || Memory::learn_par(corpus.par_iter().cloned()));
+    println!(
+        "learn_par on {} threads: {:?} ({:.0} lines/s), a speedup of {:.2}x",
+        threads,
+        parallel_time,
+        common::lines_per_second(lines, parallel_time),
+        sequential_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+
+    assert_eq!(sequential.state_count(), parallel.state_count());
+    assert_eq!(sequential.transition_count(), parallel.transition_count());
+}
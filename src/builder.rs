@@ -0,0 +1,148 @@
+use crate::memory::{valid_novelty_penalty, Config};
+use crate::{LongTokens, Memory, Normalization, Tokenizer};
+
+/// Configures a new, empty `Memory` with chained setters. Every setting that isn't set keeps the default of `Memory::default()`.
+///
+/// E.g. `MemoryBuilder::new().order(3).case_sensitive(true).build()` creates a case sensitive chain that looks at the last 3 parts of a sentence.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBuilder {
+    config: Config,
+}
+
+impl MemoryBuilder {
+    /// Start configuring a chain with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look at the last `order` parts of a sentence to pick the next word, see `Memory::with_order`.
+    ///
+    /// Panics if `order` is 0.
+    pub fn order(mut self, order: usize) -> Self {
+        assert!(order > 0, "The order of a markov chain must be at least 1");
+        self.config.order = order;
+        self
+    }
+
+    /// Learn and look up words with their original casing, see `Memory::case_sensitive`.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.config.normalization = if case_sensitive {
+            Normalization::None
+        } else {
+            Normalization::default()
+        };
+        self
+    }
+
+    /// Normalize words with the given `normalization` before learning or looking them up, see `Memory::with_normalization`. This replaces the setting of `MemoryBuilder::case_sensitive`.
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.config.normalization = normalization;
+        self
+    }
+
+    /// Remember the original casing of words for display, see `Memory::with_display_casing`.
+    pub fn display_casing(mut self, display_casing: bool) -> Self {
+        self.config.display_casing = display_casing;
+        self
+    }
+
+    /// Split lines into words with the given `tokenizer`, see `Memory::with_tokenizer`.
+    pub fn tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.config.tokenizer = tokenizer;
+        self
+    }
+
+    /// Keep at most `max_vocabulary` distinct words, see `Memory::with_max_vocabulary`.
+    pub fn max_vocabulary(mut self, max_vocabulary: usize) -> Self {
+        self.config.max_vocabulary = Some(max_vocabulary);
+        self
+    }
+
+    /// Limit the length of learned words, see `Memory::set_max_token_len`.
+    pub fn max_token_len(mut self, max_token_len: usize, long_tokens: LongTokens) -> Self {
+        self.config.max_token_len = Some(max_token_len);
+        self.config.long_tokens = long_tokens;
+        self
+    }
+
+    /// Set the chance to break off a sentence, see `Memory::set_break_chance`.
+    pub fn break_chance(mut self, break_chance_per_three_words: u32) -> Self {
+        self.config.break_chance_per_three_words = break_chance_per_three_words.min(100);
+        self
+    }
+
+    /// Continue generated sentences with a new line where a learned line ended, see `Memory::set_continue_across_lines`.
+    pub fn continue_across_lines(mut self, continue_across_lines: bool) -> Self {
+        self.config.continue_across_lines = continue_across_lines;
+        self
+    }
+
+    /// Set the amount of words that are added before a sentence may be randomly broken off, see `Memory::set_min_words`.
+    pub fn min_words(mut self, min_words: usize) -> Self {
+        self.config.min_words = min_words;
+        self
+    }
+
+    /// Set how much words that are already in a generated sentence are avoided, see `Memory::set_novelty_penalty`.
+    pub fn novelty_penalty(mut self, novelty_penalty: f64) -> Self {
+        self.config.novelty_penalty = valid_novelty_penalty(novelty_penalty);
+        self
+    }
+
+    /// Keep an index of the contexts that lead to every context while learning, see `Memory::set_bidirectional`.
+    pub fn bidirectional(mut self, bidirectional: bool) -> Self {
+        self.config.bidirectional = bidirectional;
+        self
+    }
+
+    /// Ignore lines that are split into fewer words than `min_tokens_to_learn`, see `Memory::set_min_tokens_to_learn`.
+    pub fn min_tokens_to_learn(mut self, min_tokens_to_learn: usize) -> Self {
+        self.config.min_tokens_to_learn = min_tokens_to_learn;
+        self
+    }
+
+    /// Never learn or generate any of the given `words`, see `Memory::set_banlist`.
+    pub fn banlist(mut self, words: impl IntoIterator<Item = String>) -> Self {
+        // The words are normalized once the normalization is known, see `MemoryBuilder::build`
+        self.config.banlist = words.into_iter().collect();
+        self
+    }
+
+    /// Set the maximum amount of steps of generating a single sentence, see `Memory::set_max_steps`.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.config.max_steps = max_steps;
+        self
+    }
+
+    /// Set the separator between the words of generated sentences, see `Memory::set_join_with`.
+    pub fn join_with(mut self, join_with: impl Into<String>) -> Self {
+        self.config.join_with = join_with.into();
+        self
+    }
+
+    /// Uppercase the first letter of every generated sentence, see `Memory::set_capitalize_first`.
+    pub fn capitalize_first(mut self, capitalize_first: bool) -> Self {
+        self.config.capitalize_first = capitalize_first;
+        self
+    }
+
+    /// Avoid going straight back to the context generation was in one word ago, see `Memory::set_avoid_revisits`.
+    pub fn avoid_revisits(mut self, avoid_revisits: bool) -> Self {
+        self.config.avoid_revisits = avoid_revisits;
+        self
+    }
+
+    /// Set how many times a sequence of words may repeat back to back, see `Memory::set_max_repeat`.
+    pub fn max_repeat(mut self, max_repeat: usize) -> Self {
+        self.config.max_repeat = max_repeat.max(1);
+        self
+    }
+
+    /// Create the empty chain with these settings
+    pub fn build(self) -> Memory {
+        let banlist = self.config.banlist.clone();
+        let mut memory = Memory::with_config(self.config);
+        memory.set_banlist(banlist);
+        memory
+    }
+}
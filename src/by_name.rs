@@ -0,0 +1,31 @@
+//! Serializes a value with the names of its fields, even in formats like bincode that only save the values in order, so a field with `#[serde(default)]` can be added to the value without breaking files saved without it.
+//!
+//! Use with `#[serde(with = "crate::by_name")]`. Human readable formats like JSON already save the names, so the value is serialized as usual, other formats save the value as JSON text.
+
+use serde::de::{DeserializeOwned, Deserializer, Error as _};
+use serde::ser::{Error as _, Serialize, Serializer};
+use serde::Deserialize;
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    if serializer.is_human_readable() {
+        return value.serialize(serializer);
+    }
+    let json = serde_json::to_string(value).map_err(S::Error::custom)?;
+    serializer.serialize_str(&json)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>,
+{
+    if deserializer.is_human_readable() {
+        return T::deserialize(deserializer);
+    }
+    let json = String::deserialize(deserializer)?;
+    serde_json::from_str(&json).map_err(D::Error::custom)
+}
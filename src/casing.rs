@@ -0,0 +1,116 @@
+use crate::words::map_heap_size;
+use crate::Map;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Keeps track of how often every original casing of a word was seen, so lowercased words can be displayed with their most common casing
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct Casings {
+    #[serde(serialize_with = "serialize_sorted")]
+    forms: Map<String, Map<String, usize>>,
+}
+
+/// Serialize the casings sorted by word, so equal casings always serialize to the same bytes
+fn serialize_sorted<S: Serializer>(
+    forms: &Map<String, Map<String, usize>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: BTreeMap<_, BTreeMap<_, _>> = forms
+        .iter()
+        .map(|(normalized, forms)| (normalized, forms.iter().collect()))
+        .collect();
+    sorted.serialize(serializer)
+}
+
+impl Casings {
+    /// Count the `original` casing of the `normalized` word
+    pub fn count(&mut self, normalized: &str, original: &str) {
+        let count = self
+            .forms
+            .entry(normalized.to_owned())
+            .or_default()
+            .entry(original.to_owned())
+            .or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    /// Remove a single count of the `original` casing of the `normalized` word. Casings that were never counted are ignored.
+    pub fn uncount(&mut self, normalized: &str, original: &str) {
+        if let Some(forms) = self.forms.get_mut(normalized) {
+            if let Some(count) = forms.get_mut(original) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    forms.remove(original);
+                }
+            }
+            if forms.is_empty() {
+                self.forms.remove(normalized);
+            }
+        }
+    }
+
+    /// Add the counts of `other` to these casings
+    pub fn merge(&mut self, other: &Casings) {
+        for (normalized, forms) in &other.forms {
+            let own_forms = self.forms.entry(normalized.clone()).or_default();
+            for (original, count) in forms {
+                let own_count = own_forms.entry(original.clone()).or_insert(0);
+                *own_count = own_count.saturating_add(*count);
+            }
+        }
+    }
+
+    /// Get the counts that grew since `baseline`, by how much they grew
+    pub fn growth_since(&self, baseline: &Casings) -> Casings {
+        let mut result = Casings::default();
+        for (normalized, forms) in &self.forms {
+            let baseline_forms = baseline.forms.get(normalized);
+            for (original, &count) in forms {
+                let baseline_count = baseline_forms
+                    .and_then(|forms| forms.get(original))
+                    .copied()
+                    .unwrap_or(0);
+                if count > baseline_count {
+                    result
+                        .forms
+                        .entry(normalized.clone())
+                        .or_default()
+                        .insert(original.clone(), count - baseline_count);
+                }
+            }
+        }
+        result
+    }
+
+    /// Forget every counted casing, but keep the allocated memory
+    pub fn clear(&mut self) {
+        self.forms.clear();
+    }
+
+    /// Forget the casings of every normalized word `keep` returns false for
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.forms.retain(|normalized, _| keep(normalized));
+    }
+
+    /// Estimate the amount of bytes these casings have allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        map_heap_size(&self.forms)
+            + self
+                .forms
+                .iter()
+                .map(|(normalized, forms)| {
+                    normalized.capacity()
+                        + map_heap_size(forms)
+                        + forms.keys().map(String::capacity).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    /// Get the most common casing of the `normalized` word. Returns the `normalized` word itself if no casing was counted.
+    pub fn display<'a>(&'a self, normalized: &'a str) -> &'a str {
+        self.forms
+            .get(normalized)
+            .and_then(|forms| forms.iter().max_by_key(|(_, &count)| count))
+            .map_or(normalized, |(original, _)| original.as_str())
+    }
+}
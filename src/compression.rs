@@ -0,0 +1,84 @@
+/// The way a chain is compressed when it's saved with `Memory::save_with_options`. More ways may be added, so matching on it needs a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Don't compress the chain at all. This is the fastest option, but produces the biggest files
+    Stored,
+
+    /// Compress the chain with deflate, optionally with a level from 0 (fastest) to 9 (smallest). Higher levels are clamped to 9. Without a level, the default level of 6 is used
+    Deflated {
+        /// The compression level, from 0 to 9
+        level: Option<u32>,
+    },
+
+    /// Compress the chain with zstd, optionally with a level from 1 (fastest) to 22 (smallest). Levels outside of that range are clamped to it. Without a level, the default level of 3 is used. The default level saves several times faster than deflate at a similar size, and high levels produce much smaller files.
+    ///
+    /// Only available with the `zstd-compression` feature. Files saved with it can only be loaded with that feature enabled.
+    #[cfg(feature = "zstd-compression")]
+    Zstd {
+        /// The compression level, from 1 to 22
+        level: Option<i32>,
+    },
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::Deflated { level: None }
+    }
+}
+
+impl Compression {
+    /// The zip options that produce this compression
+    pub(crate) fn file_options(self) -> zip::write::FileOptions {
+        let options = zip::write::FileOptions::default();
+        match self {
+            Compression::Stored => options.compression_method(zip::CompressionMethod::Stored),
+            Compression::Deflated { level } => options
+                .compression_method(zip::CompressionMethod::Deflated)
+                .compression_level(level.map(|level| level.min(9) as i32)),
+            #[cfg(feature = "zstd-compression")]
+            Compression::Zstd { level } => options
+                .compression_method(zip::CompressionMethod::Zstd)
+                .compression_level(level.map(|level| level.clamp(1, 22))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "zstd-compression"))]
+mod tests {
+    use super::*;
+    use crate::Memory;
+
+    /// Save the chain with the given compression, and load it again. Returns the size of the file.
+    fn save_and_load(memory: &Memory, compression: Compression, name: &str) -> u64 {
+        let path = std::env::temp_dir().join(format!("markov-{}-{}.zip", name, std::process::id()));
+        memory.save_with_options(&path, compression).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        let loaded = Memory::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.state_count(), memory.state_count());
+        assert_eq!(loaded.transition_count(), memory.transition_count());
+        size
+    }
+
+    #[test]
+    fn zstd_and_deflate_compress() {
+        let mut memory = Memory::default();
+        for i in 0..10_000 {
+            memory.learn(&format!(
+                "word{} word{} word{} word{}",
+                i % 97,
+                i % 89,
+                i % 83,
+                i % 13
+            ));
+        }
+        let stored = save_and_load(&memory, Compression::Stored, "stored");
+        let deflate = save_and_load(&memory, Compression::default(), "deflate");
+        let zstd = save_and_load(&memory, Compression::Zstd { level: None }, "zstd");
+        let zstd_max = save_and_load(&memory, Compression::Zstd { level: Some(22) }, "zstd-max");
+        assert!(deflate < stored);
+        assert!(zstd < stored);
+        assert!(zstd_max <= zstd);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{Memory, Part, SentenceContext};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// The options of `Memory::to_dot`, which limit how much of the chain is drawn
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// The minimum amount of times a transition must have been seen to be drawn as an edge
+    pub min_count: usize,
+    /// The maximum amount of contexts to draw as nodes. The most common contexts are kept, None draws every context
+    pub max_nodes: Option<usize>,
+}
+
+/// Write `memory` as a Graphviz DOT graph to `writer`, see `Memory::to_dot`
+pub(crate) fn write_dot<W: Write>(
+    memory: &Memory,
+    mut writer: W,
+    options: &DotOptions,
+) -> io::Result<()> {
+    let mut contexts = memory
+        .words
+        .iter()
+        .map(|(context, next)| (label(memory, context), context, next))
+        .collect::<Vec<_>>();
+    // Sort by how common the context is, and by label for a stable output
+    contexts.sort_by(|(a_label, _, a), (b_label, _, b)| {
+        b.total().cmp(&a.total()).then_with(|| a_label.cmp(b_label))
+    });
+    if let Some(max_nodes) = options.max_nodes {
+        contexts.truncate(max_nodes);
+    }
+    let ids = contexts
+        .iter()
+        .enumerate()
+        .map(|(id, (_, context, _))| (*context, id))
+        .collect::<HashMap<&SentenceContext, usize>>();
+
+    writeln!(writer, "digraph markov {{")?;
+    writeln!(writer, "    start [label=\"START\", shape=Mdiamond];")?;
+    writeln!(writer, "    end [label=\"END\", shape=Msquare];")?;
+    for (label, context, _) in &contexts {
+        writeln!(
+            writer,
+            "    n{} [label=\"{}\"];",
+            ids[context],
+            escape(label)
+        )?;
+    }
+
+    for (_, context, next) in &contexts {
+        let id = ids[context];
+        if context.start_word().is_some() && next.total() >= options.min_count {
+            writeln!(writer, "    start -> n{} [label=\"{}\"];", id, next.total())?;
+        }
+        for (&part, count) in next.iter() {
+            if count < options.min_count {
+                continue;
+            }
+            match part {
+                Part::EndOfLine => {
+                    writeln!(writer, "    n{} -> end [label=\"{}\"];", id, count)?;
+                }
+                Part::Word(_) => {
+                    let mut next_context = (*context).clone();
+                    next_context.shift_part(part);
+                    // The next context may have been left out by `max_nodes`
+                    if let Some(next_id) = ids.get(&next_context) {
+                        writeln!(writer, "    n{} -> n{} [label=\"{}\"];", id, next_id, count)?;
+                    }
+                }
+                // Words outside of the vocabulary lead nowhere
+                Part::StartOfLine | Part::Unknown => {}
+            }
+        }
+    }
+    writeln!(writer, "}}")
+}
+
+/// The words of `context` separated by spaces. __START__ is left out, as it's drawn as an edge from the start node
+fn label(memory: &Memory, context: &SentenceContext) -> String {
+    context
+        .parts()
+        .iter()
+        .filter_map(|part| match *part {
+            Part::Word(word) => Some(memory.display(memory.word(word))),
+            Part::Unknown => Some("?"),
+            Part::StartOfLine | Part::EndOfLine => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Escape `label` for use in a quoted DOT string
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
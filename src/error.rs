@@ -1,3 +1,5 @@
+use crate::SentencePart;
+
 /// All the errors this crate can throw
 #[derive(Debug)]
 pub enum Error {
@@ -10,9 +12,29 @@ pub enum Error {
     /// Could not read the file as a valid zip
     CouldNotReadZip(zip::result::ZipError),
 
-    /// Could not find a valid file in a given zip
+    /// Could not read the memory entry in a given zip
     CouldNotReadFirstFile(zip::result::ZipError),
 
+    /// The given zip does not contain a `memory.dat` entry
+    MissingMemoryEntry,
+
+    /// The given zip does not contain the requested extra entry, see `Memory::read_entry`
+    MissingEntry(String),
+
+    /// An extra entry can't be saved with the given name, as the chain itself uses it, see `Memory::save_with_entries`
+    ReservedEntryName(String),
+
+    /// The version header of the given zip is corrupt
+    InvalidVersionHeader,
+
+    /// The given zip was saved in a format version this crate can't load, e.g. by a newer version of this crate
+    UnsupportedVersion {
+        /// The format version of the file
+        found: u32,
+        /// The newest format version this crate can load. Every version up to it can be loaded
+        newest: u32,
+    },
+
     /// Could not create a zip entry while saving a memory
     CouldNotCreateZipEntry(zip::result::ZipError),
 
@@ -21,4 +43,209 @@ pub enum Error {
 
     /// Serialize error while saving a memory, should never occur
     CouldNotSerialize(bincode::Error),
+
+    /// Could not export or import a memory as JSON
+    Json(serde_json::Error),
+
+    /// The loaded memory was saved with a different order than the one that was requested, or a memory with a different order was merged, see `Memory::merge_checked`
+    OrderMismatch {
+        /// The order that was requested, or the order of the memory that was merged into
+        expected: usize,
+        /// The order of the loaded or merged memory
+        found: usize,
+    },
+
+    /// The loaded memory has an order of 0, which can't look at any part of a sentence, so the file is corrupt
+    InvalidOrder(usize),
+
+    /// The loaded memory refers to a word that it doesn't contain, so the file is corrupt
+    UnknownWordId {
+        /// The id of the missing word
+        id: u32,
+        /// The amount of words the memory contains, which are numbered from 0
+        words: usize,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::CouldNotOpenFile(e) => write!(f, "Could not open memory file: {}", e),
+            Error::CouldNotCreateFile(e) => write!(f, "Could not create memory file: {}", e),
+            Error::CouldNotReadZip(e) => write!(f, "Could not read memory file as a zip: {}", e),
+            Error::CouldNotReadFirstFile(e) => {
+                write!(
+                    f,
+                    "Could not read the memory entry of the memory zip: {}",
+                    e
+                )
+            }
+            Error::MissingMemoryEntry => write!(f, "The memory zip has no memory.dat entry"),
+            Error::MissingEntry(name) => write!(f, "The memory zip has no {} entry", name),
+            Error::ReservedEntryName(name) => {
+                write!(f, "The {} entry of the memory zip is reserved", name)
+            }
+            Error::InvalidVersionHeader => {
+                write!(f, "The version header of the memory zip is corrupt")
+            }
+            Error::UnsupportedVersion { found, newest } => write!(
+                f,
+                "The memory zip has format version {}, but only versions up to {} are supported",
+                found, newest
+            ),
+            Error::CouldNotCreateZipEntry(e) => {
+                write!(f, "Could not create an entry in the memory zip: {}", e)
+            }
+            Error::CouldNotDeserialize(e) => write!(f, "Could not deserialize memory: {}", e),
+            Error::CouldNotSerialize(e) => write!(f, "Could not serialize memory: {}", e),
+            Error::Json(e) => write!(f, "Could not convert memory to or from JSON: {}", e),
+            Error::OrderMismatch { expected, found } => write!(
+                f,
+                "Expected a memory with order {}, but found a memory with order {}",
+                expected, found
+            ),
+            Error::InvalidOrder(order) => write!(
+                f,
+                "The memory has order {}, but the order must be at least 1",
+                order
+            ),
+            Error::UnknownWordId { id, words } => write!(
+                f,
+                "The memory refers to word {}, but only contains {} words",
+                id, words
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CouldNotOpenFile(e) | Error::CouldNotCreateFile(e) => Some(e),
+            Error::CouldNotReadZip(e)
+            | Error::CouldNotReadFirstFile(e)
+            | Error::CouldNotCreateZipEntry(e) => Some(e),
+            Error::CouldNotDeserialize(e) | Error::CouldNotSerialize(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::MissingMemoryEntry
+            | Error::MissingEntry(_)
+            | Error::ReservedEntryName(_)
+            | Error::InvalidVersionHeader
+            | Error::UnsupportedVersion { .. }
+            | Error::OrderMismatch { .. }
+            | Error::InvalidOrder(_)
+            | Error::UnknownWordId { .. } => None,
+        }
+    }
+}
+
+/// The reasons `Memory::try_speak` can fail to produce a sentence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeakError {
+    /// The starting word is empty
+    EmptyInput,
+
+    /// The starting word contains whitespace, so it's multiple words
+    ContainsWhitespace,
+
+    /// The starting word was never followed by another word
+    NoTransitions,
+}
+
+impl std::fmt::Display for SpeakError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SpeakError::EmptyInput => write!(f, "The starting word is empty"),
+            SpeakError::ContainsWhitespace => write!(f, "The starting word is more than one word"),
+            SpeakError::NoTransitions => {
+                write!(f, "The starting word was never followed by another word")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpeakError {}
+
+/// A broken invariant of a chain, which `Memory::validate` found. Chains that were only changed by this crate never have one, but corrupted or edited files can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The chain has an order of 0, so it can't look at any part of a sentence, and learning a line panics
+    InvalidOrder(usize),
+
+    /// The context has a different amount of parts than the order of the chain
+    WrongContextLength {
+        /// The context, oldest part first
+        context: Vec<SentencePart>,
+        /// The order of the chain
+        expected: usize,
+    },
+
+    /// The context has a start or end of a line where it can't be. Only the oldest parts of a context may be the start of a line, and it must end in a word
+    MisplacedSentinel {
+        /// The context, oldest part first
+        context: Vec<SentencePart>,
+    },
+
+    /// The context has no parts that follow it
+    NoFollowUps {
+        /// The context, oldest part first
+        context: Vec<SentencePart>,
+    },
+
+    /// A part that follows the context was counted 0 times
+    ZeroCount {
+        /// The context, oldest part first
+        context: Vec<SentencePart>,
+        /// The part that follows the context
+        part: SentencePart,
+    },
+
+    /// The start of a line follows the context, which can't happen
+    StartAfterContext {
+        /// The context, oldest part first
+        context: Vec<SentencePart>,
+    },
+
+    /// A word id is used that doesn't belong to any word of the chain. The word shows up as `SentencePart::Unknown` in the other errors
+    UnknownWordId(u32),
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::InvalidOrder(order) => write!(
+                f,
+                "The chain has order {}, but the order must be at least 1",
+                order
+            ),
+            ValidationError::WrongContextLength { context, expected } => write!(
+                f,
+                "The context {:?} has {} parts, but the order of the chain is {}",
+                context,
+                context.len(),
+                expected
+            ),
+            ValidationError::MisplacedSentinel { context } => write!(
+                f,
+                "The context {:?} has the start or end of a line in the wrong place",
+                context
+            ),
+            ValidationError::NoFollowUps { context } => {
+                write!(f, "The context {:?} is never followed by anything", context)
+            }
+            ValidationError::ZeroCount { context, part } => write!(
+                f,
+                "{:?} follows the context {:?}, but was counted 0 times",
+                part, context
+            ),
+            ValidationError::StartAfterContext { context } => {
+                write!(f, "The start of a line follows the context {:?}", context)
+            }
+            ValidationError::UnknownWordId(id) => {
+                write!(f, "The word id {} doesn't belong to any word", id)
+            }
+        }
+    }
 }
+
+impl std::error::Error for ValidationError {}
@@ -0,0 +1,124 @@
+//! A read-only copy of a chain that generates sentences without the standard library, e.g. on a microcontroller.
+
+use crate::part::{pick_by_count, repeats_too_often};
+use crate::tokenizer::capitalize_first;
+use crate::{Normalization, Part, Tokenizer, WordId};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The parts that followed a context, with how often they did
+pub(crate) type FrozenNextParts = Vec<(Part, usize)>;
+
+/// A read-only copy of a chain, which can only generate sentences. It's the only part of this crate that is available without the `std` feature, so a trained chain can be embedded in firmware.
+///
+/// Create one with `Memory::freeze` where the standard library is available, serialize it with any serde format that works without `std`, and deserialize it on the target. Instead of hash maps, every lookup is a binary search in a sorted list.
+///
+/// The copy always picks words by their counts, like `Memory::speak`. The novelty penalty, continuing across lines and avoiding revisits are ignored, see `Memory::set_novelty_penalty`, `Memory::set_continue_across_lines` and `Memory::set_avoid_revisits`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FrozenChain {
+    pub(crate) order: usize,
+    pub(crate) normalization: Normalization,
+    pub(crate) tokenizer: Tokenizer,
+    pub(crate) break_chance_per_three_words: u32,
+    pub(crate) max_repeat: usize,
+    pub(crate) min_words: usize,
+    pub(crate) max_steps: usize,
+    pub(crate) join_with: String,
+    pub(crate) capitalize_first: bool,
+    /// Every word by its id, with the casing it is displayed with
+    pub(crate) words: Vec<String>,
+    /// The id of every normalized word, sorted by word
+    pub(crate) ids: Vec<(String, WordId)>,
+    /// Every context, oldest part first, with the parts that followed it and how often they did, sorted by context
+    pub(crate) contexts: Vec<(Vec<Part>, FrozenNextParts)>,
+}
+
+impl FrozenChain {
+    /// The amount of parts this chain looks at to pick the next word.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak_with_rng`. Returns None if the starting word was never followed by another word.
+    pub fn speak<R: Rng>(&self, starting_word: &str, rng: &mut R) -> Option<String> {
+        let starting_word = self.normalization.apply(starting_word);
+        let id = self
+            .ids
+            .binary_search_by(|(word, _)| word.as_str().cmp(&starting_word))
+            .ok()
+            .map(|index| self.ids[index].1)?;
+
+        // We always start with __START__, ..., starting_word
+        let mut context = vec![Part::StartOfLine; self.order];
+        shift(&mut context, Part::Word(id));
+        let mut result = self.words[id as usize].clone();
+        let mut history = Vec::new();
+        loop {
+            if history.len() >= self.max_steps || self.should_break(history.len(), rng) {
+                break;
+            }
+            let next = match self.next_parts(&context) {
+                Some(next) => next,
+                None => break,
+            };
+            let part = match pick_by_count(rng, next.iter().copied()) {
+                Some(part) => part,
+                None => break,
+            };
+            match part {
+                Part::Word(id) => {
+                    history.push(id);
+                    if repeats_too_often(&history, self.max_repeat) {
+                        break;
+                    }
+                    let word = &self.words[id as usize];
+                    if !self.tokenizer.is_punctuation(word) {
+                        result.push_str(&self.join_with);
+                    }
+                    result.push_str(word);
+                }
+                // The end of the line, or a word outside of the vocabulary, which was never followed by anything
+                _ => break,
+            }
+            shift(&mut context, part);
+        }
+        if history.is_empty() {
+            return None;
+        }
+        if self.capitalize_first {
+            capitalize_first(&mut result);
+        }
+        Some(result)
+    }
+
+    /// The parts that followed the given context, with how often they did
+    fn next_parts(&self, context: &[Part]) -> Option<&[(Part, usize)]> {
+        let index = self
+            .contexts
+            .binary_search_by(|(other, _)| other.as_slice().cmp(context))
+            .ok()?;
+        Some(&self.contexts[index].1)
+    }
+
+    /// Randomly decides if the sentence should end after `len` generated words, like `Memory::speak`
+    fn should_break(&self, len: usize, rng: &mut impl Rng) -> bool {
+        if len < self.min_words {
+            return false;
+        }
+        let chance_to_break = (len / 3)
+            .saturating_mul(self.break_chance_per_three_words as usize)
+            .min(100);
+        rng.gen_bool(chance_to_break as f64 / 100.0)
+    }
+}
+
+/// Push `part` onto the end of `context`, dropping the oldest part
+fn shift(context: &mut [Part], part: Part) {
+    context.rotate_left(1);
+    if let Some(last) = context.last_mut() {
+        *last = part;
+    }
+}
@@ -0,0 +1,375 @@
+use crate::part::{pick_by_count, repeats_too_often};
+use crate::steering::Steering;
+use crate::{Memory, NextPartList, Part, Sampler, SentenceContext, SentencePart, WordId};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// A context, oldest part first, together with the part that was picked to follow it, see `Memory::speak_traced`
+pub type Decision = (Vec<SentencePart>, SentencePart);
+
+/// Why a generated sentence ended, see `Memory::speak_detailed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The end of a line was picked, where a learned line ended
+    NaturalEnd,
+    /// The chain never learned what follows the last words, or nothing that followed them could be picked
+    DeadEnd,
+    /// The sentence was randomly broken off, see `Memory::set_break_chance`
+    RandomBreak,
+    /// The maximum amount of words was reached, e.g. that of `Memory::speak_bounded`
+    MaxLength,
+    /// The next word would have repeated a sequence of words too often, see `Memory::set_max_repeat`
+    RepeatGuard,
+    /// Generation took the maximum amount of steps, see `Memory::set_max_steps`
+    MaxSteps,
+}
+
+/// A sentence that is generated in chunks, so generation can be resumed where it left off, see `Memory::continue_speak`
+#[derive(Debug, Clone)]
+pub struct GenerationState {
+    /// The last parts of the sentence so far
+    context: SentenceContext,
+    /// The words that were generated so far, not counting the starting word
+    history: Vec<WordId>,
+    /// Why the sentence ended, once it did for any other reason than being broken off
+    stop_reason: Option<StopReason>,
+}
+
+impl GenerationState {
+    pub(crate) fn new(context: SentenceContext) -> Self {
+        Self {
+            context,
+            history: Vec::new(),
+            stop_reason: None,
+        }
+    }
+
+    /// The amount of words that were generated so far, not counting the starting word.
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Checks if no words were generated yet.
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Checks if the sentence can't be continued anymore, e.g. because the end of a line was picked. Sentences that were randomly broken off can always be continued.
+    pub fn is_finished(&self) -> bool {
+        self.stop_reason.is_some()
+    }
+
+    /// Why the sentence can't be continued anymore, or None if it can.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+}
+
+/// The options of a single `Memory::speak` call, see `Memory::speak_with_options`. Set the options that differ from the defaults, and leave the rest to `..SpeakOptions::default()`.
+#[derive(Debug, Clone, Default)]
+pub struct SpeakOptions {
+    /// The maximum amount of words to add to the starting word
+    pub max_words: Option<usize>,
+    /// The way every next word is picked
+    pub sampler: Sampler,
+    /// Whether to only end the sentence once the end of a line is picked, instead of randomly breaking it off. Only `max_words` limits the length of the sentence then, see `Memory::speak_natural`
+    pub natural_ending: bool,
+    /// Whether to back off to a shorter context when the current context is unknown, see `Memory::speak_smoothed`
+    pub smoothing: bool,
+    /// Whether to only pick a word as the first part after the starting word, so the sentence doesn't end right away, see `Memory::speak_nonempty`
+    pub nonempty: bool,
+}
+
+/// Lazily picks random follow-up words for a context, as configured by the `SpeakOptions`. This is the generation loop behind `Memory::speak` and friends.
+pub struct Generator<'a, R> {
+    memory: &'a Memory,
+    previous_context: SentenceContext,
+    /// The context before `previous_context`, which is only kept if revisiting it is avoided, see `Memory::set_avoid_revisits`
+    last_context: Option<SentenceContext>,
+    rng: R,
+    options: SpeakOptions,
+    /// The words that were generated so far
+    history: Vec<WordId>,
+    /// The amount of words of `history` that were generated before generation was resumed
+    resumed_at: usize,
+    /// The distinct words of `history`, which are avoided according to the novelty penalty
+    used: HashSet<WordId>,
+    /// The amount of unknown words that were skipped
+    skipped: usize,
+    /// The amount of parts that were picked, see `Memory::set_max_steps`
+    steps: usize,
+    /// The contexts grouped by their last part, which is only built once generation has to back off
+    backoff: Option<HashMap<Part, Vec<(&'a SentenceContext, usize)>>>,
+    /// Every context together with the part that was picked for it, if the decisions are traced
+    trace: Option<Vec<Decision>>,
+    /// The word to steer toward, until it's generated
+    steering: Option<Steering>,
+    /// Why generation ended, once it did
+    stop_reason: Option<StopReason>,
+    done: bool,
+}
+
+impl<'a, R: Rng> Generator<'a, R> {
+    pub fn new(
+        memory: &'a Memory,
+        previous_context: SentenceContext,
+        rng: R,
+        options: SpeakOptions,
+    ) -> Self {
+        // The words the sentence starts with are already used
+        let used = previous_context
+            .parts()
+            .iter()
+            .filter_map(|part| match *part {
+                Part::Word(word) => Some(word),
+                _ => None,
+            })
+            .collect();
+        Self {
+            memory,
+            previous_context,
+            last_context: None,
+            rng,
+            options,
+            history: Vec::new(),
+            resumed_at: 0,
+            used,
+            skipped: 0,
+            steps: 0,
+            backoff: None,
+            trace: None,
+            steering: None,
+            stop_reason: None,
+            done: false,
+        }
+    }
+
+    /// Continue generating the sentence of `state`, with the given rng and options. The length of the sentence, which the random break and `max_words` depend on, is counted from where the sentence is continued, so that every chunk is as long as a new sentence would be.
+    pub fn resume(
+        memory: &'a Memory,
+        state: &GenerationState,
+        rng: R,
+        options: SpeakOptions,
+    ) -> Self {
+        let mut generator = Self::new(memory, state.context.clone(), rng, options);
+        generator.used.extend(state.history.iter().copied());
+        generator.history = state.history.clone();
+        generator.resumed_at = state.history.len();
+        generator
+    }
+
+    /// Store where generation left off in `state`, so it can be resumed with `Generator::resume`
+    pub fn save_state(&self, state: &mut GenerationState) {
+        state.context = self.previous_context.clone();
+        state.history = self.history.clone();
+        state.stop_reason = self
+            .stop_reason
+            .filter(|&reason| reason != StopReason::RandomBreak);
+    }
+
+    /// Prefer words that lead to the target word of `steering`, until the target word is generated. The sentence isn't randomly broken off before that.
+    pub fn steered(mut self, steering: Steering) -> Self {
+        self.steering = Some(steering);
+        self
+    }
+
+    /// Record every context together with the part that was picked for it, see `Generator::into_trace`
+    pub fn traced(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    /// The recorded decisions, oldest first. Empty if the generator wasn't `traced`.
+    pub fn into_trace(self) -> Vec<Decision> {
+        self.trace.unwrap_or_default()
+    }
+
+    /// Why generation ended, or None if it hasn't ended yet.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// End generation for the given reason
+    fn stop(&mut self, reason: StopReason) -> Option<&'a str> {
+        self.done = true;
+        self.stop_reason = Some(reason);
+        None
+    }
+
+    /// Randomly decides if the sentence should end after the words that were generated so far, and if so, why
+    fn should_break(&mut self) -> Option<StopReason> {
+        let len = self.history.len() - self.resumed_at + self.skipped;
+        if self.options.natural_ending || self.steering.is_some() {
+            // Skipped unknown words still count towards the maximum length
+            return self
+                .options
+                .max_words
+                .filter(|&max_words| len >= max_words)
+                .map(|_| StopReason::MaxLength);
+        }
+        if self.memory.should_break(len, &mut self.rng) {
+            return Some(StopReason::RandomBreak);
+        }
+        None
+    }
+
+    /// Checks if picking `part` would go back to the context before the current one
+    fn revisits(&self, part: Part) -> bool {
+        let last = match &self.last_context {
+            Some(last) => last.parts(),
+            None => return false,
+        };
+        match last.split_last() {
+            Some((&last_part, rest)) => {
+                last_part == part && self.previous_context.parts().get(1..) == Some(rest)
+            }
+            None => false,
+        }
+    }
+
+    /// Pick the next part out of `words`, steering toward the target word if there is one
+    fn sample(&mut self, words: &'a NextPartList) -> Option<&'a Part> {
+        if let Some(steering) = &self.steering {
+            if let Some(part) = steering.pick(&self.previous_context, words, &mut self.rng) {
+                return Some(part);
+            }
+        }
+        let penalty = self.memory.config.novelty_penalty;
+        let words_only = self.options.nonempty && self.history.is_empty();
+        self.options
+            .sampler
+            .sample_penalized(words, &mut self.rng, &self.used, penalty, words_only)
+    }
+
+    /// Look up the parts that can follow the current context, and back off to another context if it is unknown and smoothing is enabled
+    fn next_parts(&mut self) -> Option<&'a NextPartList> {
+        let memory = self.memory;
+        if let Some(words) = memory.words.get(&self.previous_context) {
+            return Some(words);
+        }
+        if !self.options.smoothing {
+            return None;
+        }
+
+        // Continue from any context that ends in the same part, weighed by how often it was seen
+        let backoff = self
+            .backoff
+            .get_or_insert_with(|| memory.contexts_by_last_part());
+        let candidates = self
+            .previous_context
+            .parts()
+            .last()
+            .and_then(|last| backoff.get(last));
+        let context = match candidates {
+            Some(candidates) => pick_by_count(&mut self.rng, candidates.iter().copied())?,
+            None => memory.pick_start_context(&mut self.rng)?,
+        };
+        self.previous_context = context.clone();
+        memory.words.get(context)
+    }
+}
+
+impl<'a, R: Rng> Iterator for Generator<'a, R> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.done {
+            return None;
+        }
+
+        if self
+            .options
+            .max_words
+            .is_some_and(|max_words| self.history.len() - self.resumed_at >= max_words)
+        {
+            return self.stop(StopReason::MaxLength);
+        }
+
+        // Only continue while the combination of the last `order` words is known
+        while let Some(words) = self.next_parts() {
+            self.steps += 1;
+            if self.steps > self.memory.config.max_steps {
+                return self.stop(StopReason::MaxSteps);
+            }
+            // Try to get a random follow-up word, and try again if it is outside of the vocabulary
+            let mut next = self.sample(words);
+            for _ in 0..MAX_RESAMPLES {
+                match next {
+                    Some(Part::Unknown) => next = self.sample(words),
+                    Some(&Part::Word(word)) if self.memory.is_banned(word) => {
+                        next = self.sample(words)
+                    }
+                    Some(&part) if self.revisits(part) => next = self.sample(words),
+                    _ => break,
+                }
+            }
+            // Banned words that keep coming up end the sentence
+            if let Some(&Part::Word(word)) = next {
+                if self.memory.is_banned(word) {
+                    return self.stop(StopReason::DeadEnd);
+                }
+            }
+            if let (Some(trace), Some(&next)) = (&mut self.trace, next) {
+                let memory = self.memory;
+                let context = self.previous_context.parts().iter();
+                trace.push((
+                    context.map(|part| memory.resolve(*part)).collect(),
+                    memory.resolve(next),
+                ));
+            }
+            if self.memory.config.avoid_revisits {
+                self.last_context = Some(self.previous_context.clone());
+            }
+            let word = match next {
+                Some(&Part::Word(next_word)) => {
+                    self.previous_context.shift(next_word);
+                    next_word
+                }
+                Some(Part::EndOfLine) if self.memory.config.continue_across_lines => {
+                    // Continue with a new line, that starts like one of the learned lines
+                    let start = match self.memory.pick_start_context(&mut self.rng) {
+                        Some(start) => start,
+                        None => return self.stop(StopReason::DeadEnd),
+                    };
+                    self.previous_context = start.clone();
+                    match start.start_word() {
+                        Some(word) if !self.memory.is_banned(word) => word,
+                        _ => return self.stop(StopReason::DeadEnd),
+                    }
+                }
+                // Unknown words that keep coming up are skipped, but still count towards the length of the sentence
+                Some(Part::Unknown) => {
+                    self.previous_context.shift_part(Part::Unknown);
+                    self.skipped += 1;
+                    if let Some(reason) = self.should_break() {
+                        return self.stop(reason);
+                    }
+                    continue;
+                }
+                Some(Part::EndOfLine) => return self.stop(StopReason::NaturalEnd),
+                _ => return self.stop(StopReason::DeadEnd),
+            };
+            self.history.push(word);
+            self.used.insert(word);
+            if repeats_too_often(&self.history, self.memory.config.max_repeat) {
+                return self.stop(StopReason::RepeatGuard);
+            }
+            if self
+                .steering
+                .as_ref()
+                .is_some_and(|steering| steering.target() == word)
+            {
+                self.steering = None;
+            }
+
+            if let Some(reason) = self.should_break() {
+                self.stop(reason);
+            }
+            return Some(self.memory.word(word));
+        }
+        self.stop(StopReason::DeadEnd)
+    }
+}
+
+/// How often a new part is picked when an unknown word was picked, before that unknown word is skipped
+const MAX_RESAMPLES: usize = 3;
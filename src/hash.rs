@@ -0,0 +1,14 @@
+//! The hasher of the maps a chain is stored in. This is SipHash, the hasher of the standard library, unless the `fast-hash` feature is enabled.
+
+use std::collections::HashMap;
+
+/// A `HashMap` with the hasher of this crate
+pub type Map<K, V> = HashMap<K, V, BuildHasher>;
+
+/// Builds the hasher of a `Map`
+#[cfg(not(feature = "fast-hash"))]
+pub type BuildHasher = std::collections::hash_map::RandomState;
+
+/// Builds the hasher of a `Map`. FxHash, the hasher of rustc, is much faster than SipHash for the small keys of a chain, but it isn't randomly seeded, so text that was crafted to collide can make lookups slow.
+#[cfg(feature = "fast-hash")]
+pub type BuildHasher = std::hash::BuildHasherDefault<rustc_hash::FxHasher>;
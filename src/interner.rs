@@ -0,0 +1,131 @@
+use crate::{Map, WordId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+
+/// Stores every word of a chain once, and gives every word a small id, so the word combinations of the chain only have to store the ids.
+///
+/// Ids are handed out in order, starting at 0, and stay the same until `Interner::compact` renumbers them. Only the words are serialized, the ids are their positions.
+#[derive(Debug, Default)]
+pub struct Interner {
+    words: Vec<String>,
+    ids: Map<String, WordId>,
+}
+
+impl Interner {
+    /// Get the id of `word`, and give it a new id if it has none yet. This only allocates for new words.
+    pub fn intern(&mut self, word: &str) -> WordId {
+        if let Some(&id) = self.ids.get(word) {
+            return id;
+        }
+        let id =
+            WordId::try_from(self.words.len()).expect("A chain can't have more than 2^32 words");
+        self.words.push(word.to_owned());
+        self.ids.insert(word.to_owned(), id);
+        id
+    }
+
+    /// Get the id of `word`, if it has one
+    pub fn id(&self, word: &str) -> Option<WordId> {
+        self.ids.get(word).copied()
+    }
+
+    /// Get the word with the given `id`.
+    ///
+    /// Panics if the id was not handed out by this interner.
+    pub fn word(&self, id: WordId) -> &str {
+        &self.words[id as usize]
+    }
+
+    /// Get the word with the given `id`, if this interner handed it out
+    pub fn get(&self, id: WordId) -> Option<&str> {
+        self.words.get(id as usize).map(String::as_str)
+    }
+
+    /// The amount of words that have an id
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Iterate over every word, ordered by their ids
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(String::as_str)
+    }
+
+    /// Get a copy of this interner with the ids given out in the order of the words, together with the new id of every word, indexed by its old id. Returns None if the ids already are in that order.
+    pub fn sorted(&self) -> Option<(Interner, Vec<WordId>)> {
+        if self.words.windows(2).all(|pair| pair[0] < pair[1]) {
+            return None;
+        }
+        let mut order: Vec<WordId> = (0..self.words.len() as WordId).collect();
+        order.sort_unstable_by_key(|&id| self.word(id));
+        let mut new_ids = vec![0; order.len()];
+        let mut sorted = Interner::default();
+        for id in order {
+            new_ids[id as usize] = sorted.intern(self.word(id));
+        }
+        Some((sorted, new_ids))
+    }
+
+    /// Forget every word `keep` returns false for, and renumber the kept words, so the ids stay consecutive. The kept words keep their order.
+    ///
+    /// Returns the new id of every word, indexed by its old id, or None for the forgotten words.
+    pub fn compact(&mut self, mut keep: impl FnMut(WordId) -> bool) -> Vec<Option<WordId>> {
+        let mut next_id = 0;
+        let new_ids: Vec<_> = (0..self.words.len() as WordId)
+            .map(|id| {
+                if keep(id) {
+                    next_id += 1;
+                    Some(next_id - 1)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let mut id = 0;
+        self.words.retain(|_| {
+            id += 1;
+            new_ids[id - 1].is_some()
+        });
+        self.ids.retain(|_, id| match new_ids[*id as usize] {
+            Some(new_id) => {
+                *id = new_id;
+                true
+            }
+            None => false,
+        });
+        new_ids
+    }
+
+    /// Forget every word, but keep the allocated memory
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.ids.clear();
+    }
+
+    /// Estimate the amount of bytes this interner has allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        // Every word is stored twice, once in `words` and once as a key of `ids`
+        let words: usize = self.words.iter().map(String::capacity).sum();
+        self.words.capacity() * std::mem::size_of::<String>()
+            + crate::words::map_heap_size(&self.ids)
+            + 2 * words
+    }
+}
+
+impl Serialize for Interner {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.words.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interner {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let words = Vec::<String>::deserialize(deserializer)?;
+        let ids = words
+            .iter()
+            .enumerate()
+            .map(|(id, word)| (word.clone(), id as WordId))
+            .collect();
+        Ok(Interner { words, ids })
+    }
+}
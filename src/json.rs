@@ -0,0 +1,88 @@
+//! The JSON layout of a chain, see `Memory::export_json`. Words are written out in full wherever they are used, instead of by their ids, so the JSON can be read and edited by hand.
+
+use crate::memory::Config;
+use crate::{Casings, Error, Memory, NextPartList, SentenceContext, SentencePart};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
+
+/// The follow-up parts of a context, with how often they were counted
+type JsonNextParts = Vec<(SentencePart, usize)>;
+
+/// A chain as it is written to JSON. The word combinations are written as a list of `[context, [[part, count], ...]]` entries, sorted by their context.
+#[derive(Deserialize, Serialize)]
+pub struct JsonMemory {
+    config: Config,
+    words: Vec<(Vec<SentencePart>, JsonNextParts)>,
+    casings: Casings,
+    /// For every word that started a line, how many lines had every length, see `Memory::expected_length`
+    #[serde(default)]
+    lengths: BTreeMap<String, BTreeMap<usize, usize>>,
+}
+
+impl JsonMemory {
+    /// Write out the words of the given chain
+    pub fn new(memory: &Memory) -> Self {
+        let mut words: Vec<_> = memory
+            .words
+            .iter()
+            .map(|(context, next)| {
+                let context = context.parts().iter().map(|&part| memory.resolve(part));
+                let mut next: Vec<_> = next
+                    .iter()
+                    .map(|(&part, count)| (memory.resolve(part), count))
+                    .collect();
+                next.sort_unstable();
+                (context.collect(), next)
+            })
+            .collect();
+        words.sort_unstable();
+        let mut lengths = BTreeMap::<_, BTreeMap<_, _>>::new();
+        for (start, len, count) in memory.lengths.counts() {
+            lengths
+                .entry(memory.word(start).to_owned())
+                .or_default()
+                .insert(len, count);
+        }
+        JsonMemory {
+            config: memory.config.clone(),
+            words,
+            casings: memory.casings.clone(),
+            lengths,
+        }
+    }
+
+    /// Give every word an id again, and build the chain. Returns `Error::InvalidOrder` if the order is 0, as such a chain would panic once it learns a line.
+    pub fn into_memory(self) -> Result<Memory, Error> {
+        if self.config.order == 0 {
+            return Err(Error::InvalidOrder(self.config.order));
+        }
+        let mut memory = Memory::with_config(self.config);
+        for (context, next) in &self.words {
+            let context = context
+                .iter()
+                .map(|part| memory.intern_part(part))
+                .collect();
+            let mut next_parts = NextPartList::default();
+            for (part, count) in next {
+                next_parts.count_part_n(memory.intern_part(part), *count);
+            }
+            match memory.words.entry(SentenceContext::from_parts(context)) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge_counts(&next_parts),
+                Entry::Vacant(entry) => {
+                    entry.insert(next_parts);
+                }
+            }
+        }
+        memory.casings = self.casings;
+        for (start, lengths) in &self.lengths {
+            // Lengths of words that start no line can't be looked up
+            if let Some(start) = memory.interner.id(start) {
+                for (&len, &count) in lengths {
+                    memory.lengths.count(start, len, count);
+                }
+            }
+        }
+        Ok(memory)
+    }
+}
@@ -0,0 +1,252 @@
+//! The layouts of older versions of the saved format, so chains that were saved by older versions of this crate can still be loaded.
+
+use crate::memory::Config;
+use crate::reverse::ReverseMap;
+use crate::{
+    Casings, Interner, Lengths, Map, Memory, NextPartList, Normalization, SentenceContext,
+    SentencePart,
+};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::Read;
+
+/// The follow-up parts of a context, with how often they were counted, as they were saved before version 4
+type LegacyNextParts = Vec<(SentencePart, usize)>;
+
+/// A chain as it was saved before the saved format had a version, and always had an order of 2 and the default settings. Words were saved in their original casing, and the parts following a context were saved as a map
+#[derive(Deserialize)]
+struct BaselineMemory {
+    words: Vec<(BaselinePair, LegacyNextParts)>,
+}
+
+/// The context of a chain before the saved format had a version, with the newest part first. A chain without a version never saved unknown words.
+#[derive(Deserialize)]
+struct BaselinePair {
+    prev: SentencePart,
+    prev_prev: SentencePart,
+}
+
+impl From<BaselineMemory> for Memory {
+    fn from(baseline: BaselineMemory) -> Self {
+        let mut memory = Memory::with_order(2);
+        for (pair, next) in &baseline.words {
+            // Words are normalized now, so contexts that only differed in casing are merged
+            let context = SentenceContext::from_parts(vec![
+                memory.intern_normalized(&pair.prev_prev),
+                memory.intern_normalized(&pair.prev),
+            ]);
+            let mut next_parts = NextPartList::default();
+            for (part, count) in next {
+                next_parts.count_part_n(memory.intern_normalized(part), *count);
+            }
+            match memory.words.entry(context) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge_counts(&next_parts),
+                Entry::Vacant(entry) => {
+                    entry.insert(next_parts);
+                }
+            }
+        }
+        memory
+    }
+}
+
+/// Deserialize a chain that was saved in the given older `version` of the saved format, before version 18 saved the settings by name.
+pub fn deserialize_legacy(mut reader: impl Read, version: u32) -> bincode::Result<Memory> {
+    if version == 0 {
+        return read::<BaselineMemory>(reader).map(Memory::from);
+    }
+    let config = read_config(&mut reader, version)?;
+    let memory = match version {
+        1..=3 => read::<LegacyMemory>(reader)?.into_memory(config),
+        4..=13 => read::<LegacyInternedMemory>(reader)?.into_memory(config),
+        14..=16 => read::<LegacyMeasuredMemory<LegacyLengths>>(reader)?.into_memory(config),
+        _ => read::<LegacyMeasuredMemory<Lengths>>(reader)?.into_memory(config),
+    };
+    Ok(memory)
+}
+
+/// Deserialize a single value with bincode
+fn read<T: DeserializeOwned>(reader: impl Read) -> bincode::Result<T> {
+    bincode::deserialize_from(reader)
+}
+
+/// Read the settings of a chain that was saved in the given `version` of the saved format. These versions saved the settings without their names, in the order they were added in, so every setting is only read if it was already saved in that version. The other settings keep their defaults.
+fn read_config(reader: &mut impl Read, version: u32) -> bincode::Result<Config> {
+    let mut config = Config {
+        order: read(&mut *reader)?,
+        ..Config::default()
+    };
+    // Version 5 replaced `case_sensitive` with `Config::normalization`
+    let case_sensitive = if version < 5 {
+        Some(read::<bool>(&mut *reader)?)
+    } else {
+        config.normalization = read(&mut *reader)?;
+        None
+    };
+    config.display_casing = read(&mut *reader)?;
+    config.tokenizer = read(&mut *reader)?;
+    config.break_chance_per_three_words = read(&mut *reader)?;
+    config.max_repeat = read(&mut *reader)?;
+    if let Some(case_sensitive) = case_sensitive {
+        // The unicode tokenizers used to lowercase with the unicode rules
+        config.normalization = if case_sensitive {
+            Normalization::None
+        } else if config.tokenizer.is_unicode() {
+            Normalization::UnicodeLower
+        } else {
+            Normalization::AsciiLower
+        };
+    }
+    if version >= 2 {
+        config.max_vocabulary = read(&mut *reader)?;
+    }
+    if version >= 3 {
+        config.max_token_len = read(&mut *reader)?;
+        config.long_tokens = read(&mut *reader)?;
+    }
+    if version >= 6 {
+        config.continue_across_lines = read(&mut *reader)?;
+    }
+    if version >= 7 {
+        config.novelty_penalty = read(&mut *reader)?;
+    }
+    if version >= 8 {
+        config.min_words = read(&mut *reader)?;
+    }
+    if version >= 9 {
+        config.bidirectional = read(&mut *reader)?;
+    }
+    if version >= 10 {
+        config.min_tokens_to_learn = read(&mut *reader)?;
+    }
+    if version >= 11 {
+        config.banlist = read(&mut *reader)?;
+    }
+    if version >= 12 {
+        config.max_steps = read(&mut *reader)?;
+    }
+    if version >= 13 {
+        config.join_with = read(&mut *reader)?;
+    }
+    if version >= 15 {
+        config.capitalize_first = read(&mut *reader)?;
+    }
+    if version >= 16 {
+        config.avoid_revisits = read(&mut *reader)?;
+    }
+    Ok(config)
+}
+
+/// A chain as it was saved in versions 1 to 3 of the saved format, after its settings. Before version 4, every word was stored in full wherever it was used
+#[derive(Deserialize)]
+struct LegacyMemory {
+    words: Vec<(Vec<SentencePart>, LegacyNextParts)>,
+    casings: Casings,
+}
+
+impl LegacyMemory {
+    /// Convert this chain to the current layout, with the given settings
+    fn into_memory(self, config: Config) -> Memory {
+        let mut memory = Memory::with_config(config);
+        for (context, next) in &self.words {
+            let context = context
+                .iter()
+                .map(|part| memory.intern_part(part))
+                .collect();
+            let mut next_parts = NextPartList::default();
+            for (part, count) in next {
+                next_parts.count_part_n(memory.intern_part(part), *count);
+            }
+            memory
+                .words
+                .insert(SentenceContext::from_parts(context), next_parts);
+        }
+        memory.casings = self.casings;
+        memory
+    }
+}
+
+/// A chain as it was saved in versions 4 to 13 of the saved format, after its settings, which didn't save the lengths of learned lines
+#[derive(Deserialize)]
+struct LegacyInternedMemory {
+    interner: Interner,
+    #[serde(with = "crate::pairs")]
+    words: Map<SentenceContext, NextPartList>,
+    casings: Casings,
+}
+
+impl LegacyInternedMemory {
+    /// Convert this chain to the current layout, with the given settings
+    fn into_memory(self, config: Config) -> Memory {
+        Memory {
+            config,
+            interner: self.interner,
+            words: self.words,
+            casings: self.casings,
+            reverse: ReverseMap::default(),
+            lengths: Lengths::default(),
+            split: None,
+        }
+    }
+}
+
+/// A chain as it was saved in versions 14 to 17 of the saved format, after its settings. Versions 14 to 16 saved the lengths of learned lines as `LegacyLengths`
+#[derive(Deserialize)]
+struct LegacyMeasuredMemory<L> {
+    interner: Interner,
+    #[serde(with = "crate::pairs")]
+    words: Map<SentenceContext, NextPartList>,
+    casings: Casings,
+    reverse: ReverseMap,
+    lengths: L,
+}
+
+impl<L: IntoLengths> LegacyMeasuredMemory<L> {
+    /// Convert this chain to the current layout, with the given settings
+    fn into_memory(self, config: Config) -> Memory {
+        let lengths = self.lengths.into_lengths(&self.interner);
+        Memory {
+            config,
+            interner: self.interner,
+            words: self.words,
+            casings: self.casings,
+            reverse: self.reverse,
+            lengths,
+            split: None,
+        }
+    }
+}
+
+/// The lengths of learned lines in any version of the saved format that saved them
+trait IntoLengths {
+    /// Convert these lengths to the current layout, with the ids the `interner` of the chain gives to the starting words
+    fn into_lengths(self, interner: &Interner) -> Lengths;
+}
+
+impl IntoLengths for Lengths {
+    fn into_lengths(self, _interner: &Interner) -> Lengths {
+        self
+    }
+}
+
+/// The lengths of learned lines as they were saved before version 17, by the normalized starting word instead of its id
+#[derive(Deserialize)]
+struct LegacyLengths {
+    starts: HashMap<String, HashMap<usize, usize>>,
+}
+
+impl IntoLengths for LegacyLengths {
+    fn into_lengths(self, interner: &Interner) -> Lengths {
+        let mut result = Lengths::default();
+        for (start, lengths) in self.starts {
+            if let Some(start) = interner.id(&start) {
+                for (len, count) in lengths {
+                    result.count(start, len, count);
+                }
+            }
+        }
+        result
+    }
+}
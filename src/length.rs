@@ -0,0 +1,139 @@
+use crate::words::map_heap_size;
+use crate::{Map, WordId};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Keeps track of how many words followed the starting word of every learned line, so the typical length of a sentence can be looked up by its starting word
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct Lengths {
+    #[serde(serialize_with = "serialize_sorted")]
+    starts: Map<WordId, Map<usize, usize>>,
+}
+
+/// Serialize the lengths sorted by start word, so equal lengths always serialize to the same bytes
+fn serialize_sorted<S: Serializer>(
+    starts: &Map<WordId, Map<usize, usize>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted: BTreeMap<_, BTreeMap<_, _>> = starts
+        .iter()
+        .map(|(start, lengths)| (start, lengths.iter().collect()))
+        .collect();
+    sorted.serialize(serializer)
+}
+
+impl Lengths {
+    /// Count a line that started with the `start` word and had `len` more words, `weight` times
+    pub fn count(&mut self, start: WordId, len: usize, weight: usize) {
+        let count = self
+            .starts
+            .entry(start)
+            .or_default()
+            .entry(len)
+            .or_insert(0);
+        *count = count.saturating_add(weight);
+    }
+
+    /// Remove a single count of a line that started with `start` and had `len` more words. Lengths that were never counted are ignored.
+    pub fn uncount(&mut self, start: WordId, len: usize) {
+        if let Some(lengths) = self.starts.get_mut(&start) {
+            if let Some(count) = lengths.get_mut(&len) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    lengths.remove(&len);
+                }
+            }
+            if lengths.is_empty() {
+                self.starts.remove(&start);
+            }
+        }
+    }
+
+    /// Add the counts of `other` to these lengths, after giving every start word of `other` the id `to_own_id` returns for it
+    pub fn merge(&mut self, other: &Lengths, to_own_id: impl Fn(WordId) -> WordId) {
+        for (&start, lengths) in &other.starts {
+            let own_lengths = self.starts.entry(to_own_id(start)).or_default();
+            for (&len, count) in lengths {
+                let own_count = own_lengths.entry(len).or_insert(0);
+                *own_count = own_count.saturating_add(*count);
+            }
+        }
+    }
+
+    /// Get the counts that grew since `baseline`, by how much they grew. `to_baseline_id` returns the id `baseline` gives to a start word, if it knows the word
+    pub fn growth_since(
+        &self,
+        baseline: &Lengths,
+        to_baseline_id: impl Fn(WordId) -> Option<WordId>,
+    ) -> Lengths {
+        let mut result = Lengths::default();
+        for (&start, lengths) in &self.starts {
+            let baseline_lengths = to_baseline_id(start).and_then(|id| baseline.starts.get(&id));
+            for (&len, &count) in lengths {
+                let baseline_count = baseline_lengths
+                    .and_then(|lengths| lengths.get(&len))
+                    .copied()
+                    .unwrap_or(0);
+                if count > baseline_count {
+                    result
+                        .starts
+                        .entry(start)
+                        .or_default()
+                        .insert(len, count - baseline_count);
+                }
+            }
+        }
+        result
+    }
+
+    /// Forget every counted length, but keep the allocated memory
+    pub fn clear(&mut self) {
+        self.starts.clear();
+    }
+
+    /// Estimate the amount of bytes these lengths have allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        map_heap_size(&self.starts) + self.starts.values().map(map_heap_size).sum::<usize>()
+    }
+
+    /// Give every start word a new id, the one `f` returns for its current id
+    pub fn map_ids(&self, mut f: impl FnMut(WordId) -> WordId) -> Lengths {
+        Lengths {
+            starts: self
+                .starts
+                .iter()
+                .map(|(&start, lengths)| (f(start), lengths.clone()))
+                .collect(),
+        }
+    }
+
+    /// Give every start word the new id `f` returns for its current id, and forget the lengths of the start words it returns None for
+    pub fn remap(&mut self, mut f: impl FnMut(WordId) -> Option<WordId>) {
+        self.starts = std::mem::take(&mut self.starts)
+            .into_iter()
+            .filter_map(|(start, lengths)| Some((f(start)?, lengths)))
+            .collect();
+    }
+
+    /// Iterate over the id of every start word
+    pub fn starts(&self) -> impl Iterator<Item = WordId> + '_ {
+        self.starts.keys().copied()
+    }
+
+    /// Iterate over every counted length, as the start word, the amount of words that followed it and how many lines had that length
+    pub fn counts(&self) -> impl Iterator<Item = (WordId, usize, usize)> + '_ {
+        self.starts.iter().flat_map(|(&start, lengths)| {
+            lengths
+                .iter()
+                .map(move |(&len, &count)| (start, len, count))
+        })
+    }
+
+    /// Get the average amount of words that followed the `start` word. Returns None if no line started with it.
+    pub fn expected(&self, start: WordId) -> Option<f64> {
+        let lengths = self.starts.get(&start)?;
+        let lines: usize = lengths.values().sum();
+        let words: usize = lengths.iter().map(|(&len, &count)| len * count).sum();
+        Some(words as f64 / lines as f64)
+    }
+}
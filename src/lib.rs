@@ -1,13 +1,101 @@
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 //! This is a markov chain build with almost no previous experience with building a markov chain, on a lazy friday. This is not production-grade code. Use at own risk etc etc.
 //!
 //! The main entry point of this is `Memory`. Please see that class for more information. You can look at `main.rs` to see an implementation.
+//!
+//! Everything but `FrozenChain` needs the `std` feature, which is enabled by default. Without it, this crate only needs `alloc`, so a chain that was trained elsewhere can generate sentences on embedded targets.
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod by_name;
+#[cfg(feature = "std")]
+mod casing;
+#[cfg(feature = "std")]
+mod compression;
+#[cfg(feature = "std")]
+mod dot;
+#[cfg(feature = "std")]
 mod error;
+mod frozen;
+#[cfg(feature = "std")]
+mod generation;
+#[cfg(feature = "std")]
+mod hash;
+#[cfg(feature = "std")]
+mod interner;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod legacy;
+#[cfg(feature = "std")]
+mod length;
+#[cfg(feature = "std")]
 mod memory;
+#[cfg(feature = "std")]
+mod meta;
+#[cfg(feature = "std")]
+mod pairs;
+mod part;
+#[cfg(feature = "std")]
+mod progress;
+#[cfg(feature = "std")]
+mod reverse;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+mod steering;
+mod tokenizer;
+#[cfg(feature = "std")]
+mod version;
+#[cfg(feature = "std")]
 mod words;
 
-pub use self::error::Error;
-pub use self::memory::Memory;
-pub(crate) use self::words::{NextPartList, SentencePart, SentencePartPair};
+/// The types a chain is stored as, to experiment with custom generators or samplers on top of `Memory::next_parts` and `Memory::raw_entries`.
+///
+/// These are only available with the `unstable-internals` feature, and are not covered by semver: they may change in any release, without a major version bump.
+#[cfg(feature = "unstable-internals")]
+pub mod internals {
+    pub use crate::part::{Part, WordId};
+    pub use crate::words::{NextPartList, SentenceContext};
+}
+
+#[cfg(feature = "std")]
+pub use self::builder::MemoryBuilder;
+#[cfg(feature = "std")]
+pub(crate) use self::casing::Casings;
+#[cfg(feature = "std")]
+pub use self::compression::Compression;
+#[cfg(feature = "std")]
+pub use self::dot::DotOptions;
+#[cfg(feature = "std")]
+pub use self::error::{Error, SpeakError, ValidationError};
+pub use self::frozen::FrozenChain;
+#[cfg(feature = "std")]
+pub use self::generation::{Decision, GenerationState, SpeakOptions, StopReason};
+#[cfg(feature = "std")]
+pub(crate) use self::hash::Map;
+#[cfg(feature = "std")]
+pub(crate) use self::interner::Interner;
+#[cfg(feature = "std")]
+pub(crate) use self::length::Lengths;
+#[cfg(feature = "std")]
+pub use self::memory::{LearnStats, Memory};
+#[cfg(feature = "std")]
+pub use self::meta::Meta;
+pub(crate) use self::part::{Part, WordId};
+#[cfg(feature = "std")]
+pub use self::reverse::ReverseIndex;
+#[cfg(feature = "std")]
+pub use self::session::Session;
+pub use self::tokenizer::{LongTokens, Normalization, Tokenizer};
+#[cfg(feature = "std")]
+pub use self::words::Sampler;
+#[cfg(feature = "std")]
+pub use self::words::SentencePart;
+#[cfg(feature = "std")]
+pub(crate) use self::words::{NextPartList, SentenceContext};
@@ -1,6 +1,6 @@
 use markov::*;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::BufReader;
 use std::path::Path;
 
 fn main() {
@@ -16,17 +16,18 @@ fn main() {
         println!("Learning...");
         let logs = File::open("logs.txt").expect("Could not open logs");
         let logs_reader = BufReader::new(logs);
-        let mut count = 0;
         let start = time::precise_time_s();
-        for line in logs_reader.lines() {
-            if let Some(text) = line.as_ref().ok().and_then(|l| l.split('>').nth(1)) {
-                count += 1;
-                if count % 10000 == 0 {
-                    println!("Line {}", count);
+        let mut extracted = 0;
+        let count = memory
+            .learn_reader_with(logs_reader, |line| {
+                let text = line.split('>').nth(1)?;
+                extracted += 1;
+                if extracted % 10000 == 0 {
+                    println!("Line {}", extracted);
                 }
-                memory.learn(text);
-            }
-        }
+                Some(text)
+            })
+            .expect("Could not read logs");
         let end = time::precise_time_s();
         let time_diff = end - start;
         println!(
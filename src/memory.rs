@@ -1,120 +1,2450 @@
-use crate::{Error, NextPartList, SentencePart, SentencePartPair};
+use crate::dot;
+use crate::generation::{Decision, GenerationState, Generator, SpeakOptions, StopReason};
+use crate::json::JsonMemory;
+use crate::legacy;
+use crate::part::pick_by_count;
+use crate::progress::ProgressWriter;
+use crate::reverse::ReverseMap;
+use crate::steering::Steering;
+use crate::tokenizer::{self, Split};
+use crate::version::{self, FORMAT_VERSION, VERSION_ENTRY};
+use crate::words::{map_heap_size, most_counted};
+use crate::{
+    Casings, Compression, DotOptions, Error, FrozenChain, Interner, Lengths, LongTokens, Map,
+    MemoryBuilder, Meta, NextPartList, Normalization, Part, ReverseIndex, Sampler, SentenceContext,
+    SentencePart, Session, SpeakError, Tokenizer, ValidationError, WordId,
+};
+use rand::Rng;
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "rayon")]
+use rayon::slice::ParallelSlice;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs::File;
+use std::io::{BufRead, Read, Seek, Write};
 use std::path::Path;
-use rand::Rng;
+
+/// The name of the zip entry that contains the serialized chain
+const MEMORY_ENTRY: &str = "memory.dat";
+
+/// The name of the zip entry that contains the summary of the chain, see `Memory::load_meta`
+const META_ENTRY: &str = "meta";
+
+/// The highest order for which contexts are looked up on the stack, see `Memory::next_parts_for`
+const INLINE_ORDER: usize = 8;
 
 /// The markov chain. This contains the history of all the word combinations this chain has seen.
 ///
 /// This chain can either be created by using `Default::default()`, or loaded from a file with `Memory::load`. The chain can be saved by calling `Memory::save`
 ///
+/// By default all ascii letters are lowercased, see `Memory::with_normalization` to lowercase other letters too, `Memory::case_sensitive` to keep the original casing, or `Memory::with_display_casing` to only keep it for display.
+///
+/// By default the chain looks at the last 2 parts of a sentence to pick the next word. Use `Memory::with_order(n)` to create a chain that looks at the last `n` parts instead.
+///
 /// To learn new sentences, call `Memory::learn(line: &str)`.
 ///
 /// To get a sentence that starts with a given word, call `Memory::get(starting_word: &str)`
+///
+/// `Memory` is `Send` and `Sync`, and generating sentences never mutates it, so a learned chain can be shared between threads, e.g. in an `Arc<Memory>`, and speak from all of them at once. Use `Memory::speak_with_rng` to reuse an rng per thread instead of getting the thread local rng on every call.
 #[derive(Debug, Deserialize, Serialize, Default)]
 pub struct Memory {
-    words: HashMap<SentencePartPair, NextPartList>,
+    #[serde(with = "crate::by_name")]
+    pub(crate) config: Config,
+    pub(crate) interner: Interner,
+    #[serde(with = "crate::pairs")]
+    pub(crate) words: Map<SentenceContext, NextPartList>,
+    pub(crate) casings: Casings,
+    #[serde(default)]
+    pub(crate) reverse: ReverseMap,
+    #[serde(default)]
+    pub(crate) lengths: Lengths,
+    /// The custom predicate lines are split with, which can't be saved, see `Memory::with_split`
+    #[serde(skip)]
+    pub(crate) split: Option<Split>,
+}
+
+// `Memory` has no interior mutability, so it can be shared between threads. This fails to compile if that ever changes.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Memory>();
+};
+
+/// A chain with its word ids given out in the order of the words, which is how chains are saved, so chains that learned the same lines always save to the same bytes, no matter in which order they learned them. Serializes exactly like `Memory`, see `Memory::sorted_ids`.
+#[derive(Serialize)]
+struct SortedMemory<'a> {
+    #[serde(with = "crate::by_name")]
+    config: &'a Config,
+    interner: Interner,
+    #[serde(with = "crate::pairs")]
+    words: Map<SentenceContext, NextPartList>,
+    casings: &'a Casings,
+    reverse: ReverseMap,
+    lengths: Lengths,
+}
+
+/// The settings of a chain, which are saved together with the learned words. They are saved by name, so settings that are missing from a saved chain get their default.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    pub(crate) order: usize,
+    pub(crate) normalization: Normalization,
+    pub(crate) display_casing: bool,
+    pub(crate) tokenizer: Tokenizer,
+    pub(crate) break_chance_per_three_words: u32,
+    pub(crate) max_repeat: usize,
+    pub(crate) max_vocabulary: Option<usize>,
+    pub(crate) max_token_len: Option<usize>,
+    pub(crate) long_tokens: LongTokens,
+    pub(crate) continue_across_lines: bool,
+    pub(crate) novelty_penalty: f64,
+    pub(crate) min_words: usize,
+    pub(crate) bidirectional: bool,
+    pub(crate) min_tokens_to_learn: usize,
+    /// The normalized words that are never learned or generated, see `Memory::set_banlist`
+    pub(crate) banlist: BTreeSet<String>,
+    pub(crate) max_steps: usize,
+    /// The separator between the words of generated sentences, see `Memory::set_join_with`
+    pub(crate) join_with: String,
+    pub(crate) capitalize_first: bool,
+    pub(crate) avoid_revisits: bool,
+}
+
+/// The novelty penalty unless it's set with `Memory::set_novelty_penalty`, which doesn't change the counts
+fn no_novelty_penalty() -> f64 {
+    1.0
+}
+
+/// Clamp a novelty penalty to the penalties that can be saved, see `Memory::set_novelty_penalty`
+pub(crate) fn valid_novelty_penalty(novelty_penalty: f64) -> f64 {
+    let novelty_penalty = novelty_penalty.max(0.0);
+    if novelty_penalty.is_finite() {
+        novelty_penalty
+    } else {
+        f64::MAX
+    }
+}
+
+/// The maximum amount of steps of generating a sentence, unless it's set with `Memory::set_max_steps`
+fn default_max_steps() -> usize {
+    10_000
+}
+
+/// The separator between generated words, unless it's set with `Memory::set_join_with`
+fn default_join_with() -> String {
+    " ".to_owned()
+}
+
+/// What a single line taught a chain, see `Memory::learn_reporting`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LearnStats {
+    /// The amount of word combinations that the chain had never seen before
+    pub new_contexts: usize,
+    /// The amount of follow-up parts that were never seen after their word combination before, including those of new word combinations
+    pub new_transitions: usize,
+    /// The amount of words the line was split into
+    pub tokens: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            order: 2,
+            normalization: Normalization::default(),
+            display_casing: false,
+            tokenizer: Tokenizer::default(),
+            break_chance_per_three_words: 10,
+            max_repeat: 3,
+            max_vocabulary: None,
+            max_token_len: None,
+            long_tokens: LongTokens::default(),
+            continue_across_lines: false,
+            novelty_penalty: no_novelty_penalty(),
+            min_words: 0,
+            bidirectional: false,
+            min_tokens_to_learn: 0,
+            banlist: BTreeSet::new(),
+            max_steps: default_max_steps(),
+            join_with: default_join_with(),
+            capitalize_first: false,
+            avoid_revisits: false,
+        }
+    }
 }
 
 impl Memory {
+    /// Start configuring a new chain, see `MemoryBuilder`.
+    pub fn builder() -> MemoryBuilder {
+        MemoryBuilder::new()
+    }
+
+    /// Create an empty chain that looks at the last `order` parts of a sentence to pick the next word.
+    ///
+    /// Panics if `order` is 0.
+    pub fn with_order(order: usize) -> Memory {
+        assert!(order > 0, "The order of a markov chain must be at least 1");
+        Self::with_config(Config {
+            order,
+            ..Config::default()
+        })
+    }
+
+    /// Create an empty chain. If `case_sensitive` is true, words are learned and looked up with their original casing, instead of being lowercased.
+    pub fn case_sensitive(case_sensitive: bool) -> Memory {
+        Self::with_normalization(if case_sensitive {
+            Normalization::None
+        } else {
+            Normalization::default()
+        })
+    }
+
+    /// Create an empty chain that normalizes words with the given `normalization` before learning or looking them up. Use `Normalization::UnicodeLower` for languages with non-ascii letters, like German or Greek.
+    pub fn with_normalization(normalization: Normalization) -> Memory {
+        Self::with_config(Config {
+            normalization,
+            ..Config::default()
+        })
+    }
+
+    /// Create an empty chain that looks up words lowercased, but remembers their original casing. Generated sentences use the most common casing of every word, so "I" and names are displayed properly while "The" and "the" are still learned as the same word.
+    pub fn with_display_casing() -> Memory {
+        Self::with_config(Config {
+            display_casing: true,
+            ..Config::default()
+        })
+    }
+
+    /// Create an empty chain that keeps at most `max_vocabulary` distinct words when `Memory::limit_vocabulary` is called.
+    pub fn with_max_vocabulary(max_vocabulary: usize) -> Memory {
+        Self::with_config(Config {
+            max_vocabulary: Some(max_vocabulary),
+            ..Config::default()
+        })
+    }
+
+    /// Create an empty chain that splits lines into words with the given `tokenizer`.
+    pub fn with_tokenizer(tokenizer: Tokenizer) -> Memory {
+        Self::with_config(Config {
+            tokenizer,
+            ..Config::default()
+        })
+    }
+
+    /// Create an empty chain that splits lines into words wherever `predicate` returns true, instead of with a `Tokenizer`. E.g. `|c| !c.is_alphanumeric()` splits on whitespace and drops all punctuation. Generated sentences still join the words with spaces.
+    ///
+    /// The predicate can't be saved, so a loaded chain splits lines with its `Tokenizer` again, until the predicate is supplied again with `Memory::set_split`.
+    pub fn with_split(predicate: impl Fn(char) -> bool + Send + Sync + 'static) -> Memory {
+        let mut memory = Memory::default();
+        memory.set_split(predicate);
+        memory
+    }
+
+    /// Split lines into words wherever `predicate` returns true, see `Memory::with_split`.
+    ///
+    /// The predicate applies to everything that is learned or looked up afterwards, so it should split lines the same way as the lines that were learned before.
+    pub fn set_split(&mut self, predicate: impl Fn(char) -> bool + Send + Sync + 'static) {
+        self.split = Some(Split::new(predicate));
+    }
+
+    /// Create an empty chain that never learns or generates any of the given `words`, see `Memory::set_banlist`.
+    pub fn with_banlist(words: impl IntoIterator<Item = String>) -> Memory {
+        let mut memory = Memory::default();
+        memory.set_banlist(words);
+        memory
+    }
+
+    /// Create an empty chain with room for at least `states` contexts, see `Memory::reserve`.
+    pub fn with_capacity(states: usize) -> Memory {
+        let mut memory = Memory::default();
+        memory.reserve(states);
+        memory
+    }
+
+    /// Make room for at least `additional` more contexts, like `HashMap::reserve`. When it's roughly known how many contexts a corpus produces, e.g. from the `Memory::state_count` of an earlier run, this avoids growing the chain over and over while learning it.
+    pub fn reserve(&mut self, additional: usize) {
+        self.words.reserve(additional);
+    }
+
+    /// The amount of contexts this chain has room for without allocating more memory, see `Memory::reserve`.
+    pub fn capacity(&self) -> usize {
+        self.words.capacity()
+    }
+
+    pub(crate) fn with_config(config: Config) -> Memory {
+        Memory {
+            config,
+            interner: Interner::default(),
+            words: Map::default(),
+            casings: Casings::default(),
+            reverse: ReverseMap::default(),
+            lengths: Lengths::default(),
+            split: None,
+        }
+    }
+
+    /// The amount of parts this chain looks at to pick the next word.
+    pub fn order(&self) -> usize {
+        self.config.order
+    }
+
+    /// Set the chance, in percent, that `Memory::speak` stops the sentence after each word, for every 3 words that have been added. Defaults to 10.
+    ///
+    /// Values above 100 are clamped to 100. A value of 0 means sentences only end when the chain runs out of known words.
+    pub fn set_break_chance(&mut self, break_chance_per_three_words: u32) {
+        self.config.break_chance_per_three_words = break_chance_per_three_words.min(100);
+    }
+
+    /// Limit the length of the words that are learned to `max_token_len` characters. Longer words, like pasted links, are dropped or truncated, depending on `long_tokens`. By default the length of words is not limited.
+    pub fn set_max_token_len(&mut self, max_token_len: usize, long_tokens: LongTokens) {
+        self.config.max_token_len = Some(max_token_len);
+        self.config.long_tokens = long_tokens;
+    }
+
+    /// Set whether generated sentences continue with a new line where a learned line ended, instead of ending there. The new line starts like one of the learned lines, so the chain can ramble on across multiple sentences until it's broken off or reaches the requested amount of words. Defaults to false.
+    pub fn set_continue_across_lines(&mut self, continue_across_lines: bool) {
+        self.config.continue_across_lines = continue_across_lines;
+    }
+
+    /// Set the amount of words that `Memory::speak` adds to the starting word before it may randomly break off the sentence. Sentences can still be shorter, if the chain runs out of known words or picks the end of a line. Defaults to 0.
+    ///
+    /// A maximum amount of words, like that of `Memory::speak_bounded`, still applies, so a `min_words` above it is effectively clamped to it.
+    pub fn set_min_words(&mut self, min_words: usize) {
+        self.config.min_words = min_words;
+    }
+
+    /// Set whether this chain keeps an index of the contexts that lead to every context while learning, so `Memory::speak_backward` doesn't have to build a `ReverseIndex` on every call. The index is saved with the chain. This roughly doubles the memory the chain uses. Defaults to false.
+    ///
+    /// Turning this on builds the index for everything that was learned so far, turning it off drops the index.
+    pub fn set_bidirectional(&mut self, bidirectional: bool) {
+        self.config.bidirectional = bidirectional;
+        self.reverse = if bidirectional {
+            ReverseMap::build(&self.words)
+        } else {
+            ReverseMap::default()
+        };
+    }
+
+    /// Set the amount of words a line must be split into to be learned. Shorter lines, like "ok" or "lol" in a chat log, are ignored by `Memory::learn`, so they don't dominate the starts of generated sentences. Defaults to 0, which learns every line.
+    pub fn set_min_tokens_to_learn(&mut self, min_tokens_to_learn: usize) {
+        self.config.min_tokens_to_learn = min_tokens_to_learn;
+    }
+
+    /// Set the words that this chain never learns or generates, e.g. slurs in the chat log of a public bot. The words are normalized like learned words, so a lowercasing chain bans every casing of a word. This replaces the previous banlist.
+    ///
+    /// `Memory::learn` skips every word combination that contains a banned word, so the words around a banned word aren't connected to each other, and the line doesn't end or start there either. Words that were learned before they were banned are still in the chain, but generation picks another word instead, and ends the sentence if it keeps picking banned words.
+    pub fn set_banlist(&mut self, words: impl IntoIterator<Item = String>) {
+        let normalization = self.config.normalization;
+        self.config.banlist = words
+            .into_iter()
+            .map(|word| normalization.apply(&word).into_owned())
+            .collect();
+    }
+
+    /// Set how much words that are already in a generated sentence are avoided. Their counts are multiplied by the `novelty_penalty` while picking the next word, so a penalty of 0.5 makes them half as likely, and a penalty of 0 never picks them again, which may end the sentence early. Defaults to 1.0, which doesn't avoid any words.
+    ///
+    /// Negative values are clamped to 0, and an infinite penalty to the largest finite one, so it can be saved.
+    pub fn set_novelty_penalty(&mut self, novelty_penalty: f64) {
+        self.config.novelty_penalty = valid_novelty_penalty(novelty_penalty);
+    }
+
+    /// Set the maximum amount of steps that generating a single sentence may take, where every picked part is a step, even if it isn't added to the sentence, like a skipped unknown word. When the limit is hit, the sentence ends with the words that were generated so far. Defaults to 10000.
+    ///
+    /// The random break usually ends sentences much sooner, but this guarantees that generation ends even when it's disabled, e.g. by `Memory::speak_natural` without a maximum amount of words or by `Memory::set_continue_across_lines`.
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.config.max_steps = max_steps;
+    }
+
+    /// Set the separator that is put between the words of generated sentences, e.g. an empty string for languages that aren't written with spaces. Punctuation that the tokenizer split off is still attached to the previous word without a separator, see `Tokenizer::PunctuationAware`. Defaults to a single space.
+    pub fn set_join_with(&mut self, join_with: impl Into<String>) {
+        self.config.join_with = join_with.into();
+    }
+
+    /// Set whether the first letter of every generated sentence is uppercased, so sentences of a chain that lowercases words still start like sentences. The rest of the sentence is left as it is, so the casing of case sensitive chains is only changed at the very start. Defaults to false.
+    pub fn set_capitalize_first(&mut self, capitalize_first: bool) {
+        self.config.capitalize_first = capitalize_first;
+    }
+
+    /// Set whether generated sentences avoid going straight back to the context they were in one word ago, like "a b a b", which makes sentences oscillate between two contexts. The next word is picked again a few times if it would go back, and is only allowed if nothing else comes up. Defaults to false.
+    ///
+    /// This only looks one word back, so it's much cheaper than the repeat guard of `Memory::set_max_repeat`, but catches fewer repetitions.
+    pub fn set_avoid_revisits(&mut self, avoid_revisits: bool) {
+        self.config.avoid_revisits = avoid_revisits;
+    }
+
+    /// Set how many times a sequence of words may repeat back to back in a generated sentence, e.g. "na na na" repeats "na" 3 times. Generation stops before a word would make a sequence repeat more often. Defaults to 3.
+    ///
+    /// Only sequences of up to 4 words are checked. Values below 1 are clamped to 1.
+    pub fn set_max_repeat(&mut self, max_repeat: usize) {
+        self.config.max_repeat = max_repeat.max(1);
+    }
+
     /// Loads a markov chain from a given file. This file should be a zip of a binary representation of a previously saved chain.
+    ///
+    /// Files saved by older versions of this crate are converted, down to the very first one, which saved chains without a version. Returns `Error::UnsupportedVersion` if the file was saved by a newer version of this crate with a different format.
     pub fn load(file: impl AsRef<Path>) -> Result<Memory, Error> {
         let fs = File::open(file.as_ref()).map_err(Error::CouldNotOpenFile)?;
-        let mut reader = zip::ZipArchive::new(fs).map_err(Error::CouldNotReadZip)?;
-        let first_entry = reader.by_index(0).map_err(Error::CouldNotReadFirstFile)?;
-        let result = bincode::deserialize_from(first_entry).map_err(Error::CouldNotDeserialize)?;
-        Ok(result)
+        Self::load_from(fs)
+    }
+
+    /// Loads a markov chain from the given reader, e.g. an in-memory buffer. The data should be a zip of a binary representation of a previously saved chain.
+    pub fn load_from<R: Read + Seek>(reader: R) -> Result<Memory, Error> {
+        let mut reader = zip::ZipArchive::new(reader).map_err(Error::CouldNotReadZip)?;
+        let version = match reader.by_name(VERSION_ENTRY) {
+            Ok(mut entry) => version::read_header(&mut entry)?,
+            // Files without a version header were saved before the header was introduced
+            Err(zip::result::ZipError::FileNotFound) => 0,
+            Err(e) => return Err(Error::CouldNotReadFirstFile(e)),
+        };
+        let entry = reader.by_name(MEMORY_ENTRY).map_err(|e| match e {
+            zip::result::ZipError::FileNotFound => Error::MissingMemoryEntry,
+            e => Error::CouldNotReadFirstFile(e),
+        })?;
+        let result = if version < FORMAT_VERSION {
+            legacy::deserialize_legacy(entry, version)
+        } else {
+            bincode::deserialize_from(entry)
+        };
+        let mut memory = result.map_err(Error::CouldNotDeserialize)?;
+        memory.check_order_valid()?;
+        memory.check_word_ids()?;
+        memory.recount_totals();
+        if version < FORMAT_VERSION {
+            // The reverse map isn't read from older formats, even if they contain one
+            memory.rebuild_reverse();
+        }
+        Ok(memory)
+    }
+
+    /// Read the summary of the chain that was saved to the given file, like its order and how much it has learned, without loading the chain itself. This is much faster than `Memory::load` for big chains.
+    ///
+    /// Returns `Error::MissingEntry` if the file was saved by a version of this crate that didn't save the summary yet.
+    pub fn load_meta(file: impl AsRef<Path>) -> Result<Meta, Error> {
+        let fs = File::open(file.as_ref()).map_err(Error::CouldNotOpenFile)?;
+        Self::load_meta_from(fs)
+    }
+
+    /// Read the summary of the chain that was saved to the given reader, like `Memory::load_meta`.
+    pub fn load_meta_from<R: Read + Seek>(reader: R) -> Result<Meta, Error> {
+        let mut reader = zip::ZipArchive::new(reader).map_err(Error::CouldNotReadZip)?;
+        {
+            let mut entry = reader.by_name(VERSION_ENTRY).map_err(|e| match e {
+                zip::result::ZipError::FileNotFound => Error::MissingEntry(META_ENTRY.to_owned()),
+                e => Error::CouldNotReadFirstFile(e),
+            })?;
+            version::read_header(&mut entry)?;
+        }
+        let entry = reader.by_name(META_ENTRY).map_err(|e| match e {
+            zip::result::ZipError::FileNotFound => Error::MissingEntry(META_ENTRY.to_owned()),
+            e => Error::CouldNotReadFirstFile(e),
+        })?;
+        bincode::deserialize_from(entry).map_err(Error::CouldNotDeserialize)
+    }
+
+    /// Loads a markov chain from a given file, like `Memory::load`, but returns `Error::OrderMismatch` if the chain was not saved with the given `order`.
+    pub fn load_with_order(file: impl AsRef<Path>, order: usize) -> Result<Memory, Error> {
+        let memory = Self::load(file)?;
+        if memory.config.order != order {
+            return Err(Error::OrderMismatch {
+                expected: order,
+                found: memory.config.order,
+            });
+        }
+        Ok(memory)
     }
 
     /// Save this chain to a file. This will serialize this memory with `bincode::serialize_into`, and save that into a zip file. As such, the file extension should be `.zip`
+    ///
+    /// Everything is saved in a fixed order, so chains that learned the same lines in the same order are saved to the same bytes.
     pub fn save(&self, file: impl AsRef<Path>) -> Result<(), Error> {
         let fs = File::create(file).map_err(Error::CouldNotCreateFile)?;
-        let mut writer = zip::ZipWriter::new(fs);
-        let options =
-            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        self.save_to(fs)
+    }
+
+    /// Save this chain to the given writer, e.g. an in-memory buffer. The data is written in the same zip format as `Memory::save`.
+    pub fn save_to<W: Write + Seek>(&self, writer: W) -> Result<(), Error> {
+        self.save_to_with_options(writer, Compression::default(), &[], |_| {})
+    }
+
+    /// Save this chain to the given writer, like `Memory::save_to`, together with extra named `entries`, e.g. a `meta.json` with information about the chain. `Memory::load` ignores the extra entries, use `Memory::read_entry` to read them back.
+    ///
+    /// Returns `Error::ReservedEntryName` if an entry would replace one of the entries of the chain itself.
+    pub fn save_with_entries<W: Write + Seek>(
+        &self,
+        writer: W,
+        entries: &[(&str, &[u8])],
+    ) -> Result<(), Error> {
+        self.save_to_with_options(writer, Compression::default(), entries, |_| {})
+    }
+
+    /// Read the extra entry with the given `name` from a chain that was saved with `Memory::save_with_entries`, without loading the chain itself.
+    pub fn read_entry<R: Read + Seek>(reader: R, name: &str) -> Result<Vec<u8>, Error> {
+        let mut reader = zip::ZipArchive::new(reader).map_err(Error::CouldNotReadZip)?;
+        let mut entry = reader.by_name(name).map_err(|e| match e {
+            zip::result::ZipError::FileNotFound => Error::MissingEntry(name.to_owned()),
+            e => Error::CouldNotReadFirstFile(e),
+        })?;
+        let mut result = Vec::new();
+        entry
+            .read_to_end(&mut result)
+            .map_err(|e| Error::CouldNotReadFirstFile(e.into()))?;
+        Ok(result)
+    }
+
+    /// Save this chain to the given writer, like `Memory::save_to`, and regularly call `progress` with the amount of bytes of the chain that were written so far. This is useful to show a progress bar while saving a big chain.
+    ///
+    /// The amount of bytes is counted before compression. `progress` is called at least once, with the total amount of bytes, when saving succeeds.
+    pub fn save_with_progress<W: Write + Seek, F: FnMut(u64)>(
+        &self,
+        writer: W,
+        progress: F,
+    ) -> Result<(), Error> {
+        self.save_to_with_options(writer, Compression::default(), &[], progress)
+    }
+
+    /// Save this chain to a file, like `Memory::save`, but with the given `compression`. `Memory::load` can load the file regardless of the compression.
+    pub fn save_with_options(
+        &self,
+        file: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<(), Error> {
+        let fs = File::create(file).map_err(Error::CouldNotCreateFile)?;
+        self.save_to_with_options(fs, compression, &[], |_| {})
+    }
+
+    fn save_to_with_options<W: Write + Seek>(
+        &self,
+        writer: W,
+        compression: Compression,
+        entries: &[(&str, &[u8])],
+        progress: impl FnMut(u64),
+    ) -> Result<(), Error> {
+        if let Some((name, _)) = entries
+            .iter()
+            .find(|(name, _)| [VERSION_ENTRY, MEMORY_ENTRY, META_ENTRY].contains(name))
+        {
+            return Err(Error::ReservedEntryName((*name).to_owned()));
+        }
+        let mut writer = zip::ZipWriter::new(writer);
+        writer
+            .start_file(VERSION_ENTRY, Compression::Stored.file_options())
+            .map_err(Error::CouldNotCreateZipEntry)?;
+        version::write_header(&mut writer).map_err(|e| Error::CouldNotCreateZipEntry(e.into()))?;
+        writer
+            .start_file(MEMORY_ENTRY, compression.file_options())
+            .map_err(Error::CouldNotCreateZipEntry)?;
+        let mut progress_writer = ProgressWriter::new(&mut writer, progress);
+        self.serialize_into(&mut progress_writer)?;
+        progress_writer.finish();
         writer
-            .start_file("memory.dat", options)
+            .start_file(META_ENTRY, Compression::Stored.file_options())
             .map_err(Error::CouldNotCreateZipEntry)?;
-        bincode::serialize_into(&mut writer, self).map_err(Error::CouldNotSerialize)?;
+        bincode::serialize_into(&mut writer, &Meta::new(self, FORMAT_VERSION))
+            .map_err(Error::CouldNotSerialize)?;
+        for (name, data) in entries {
+            writer
+                .start_file(*name, compression.file_options())
+                .map_err(Error::CouldNotCreateZipEntry)?;
+            writer
+                .write_all(data)
+                .map_err(|e| Error::CouldNotCreateZipEntry(e.into()))?;
+        }
         Ok(())
     }
 
+    /// Save this chain to the given writer as plain bincode, without the zip container that `Memory::save` uses. This is useful to embed the chain in another format.
+    pub fn save_raw<W: Write>(&self, writer: W) -> Result<(), Error> {
+        self.serialize_into(writer)
+    }
+
+    /// Serialize this chain with bincode, with its word ids in the order of the words, see `Memory::sorted_ids`
+    fn serialize_into<W: Write>(&self, writer: W) -> Result<(), Error> {
+        match self.sorted_ids() {
+            Some(sorted) => bincode::serialize_into(writer, &sorted),
+            None => bincode::serialize_into(writer, self),
+        }
+        .map_err(Error::CouldNotSerialize)
+    }
+
+    /// Copy the parts of this chain that store word ids, with the ids given out in the order of the words instead of the order they were learned in. Returns None if the ids already are in that order, e.g. for a chain that was loaded and didn't learn new words since.
+    fn sorted_ids(&self) -> Option<SortedMemory<'_>> {
+        let (interner, new_ids) = self.interner.sorted()?;
+        let renumber = |part| match part {
+            Part::Word(word) => Part::Word(new_ids[word as usize]),
+            part => part,
+        };
+        let words: Map<_, _> = self
+            .words
+            .iter()
+            .map(|(context, next)| (context.map_parts(renumber), next.map_parts(renumber)))
+            .collect();
+        let reverse = if self.config.bidirectional {
+            ReverseMap::build(&words)
+        } else {
+            ReverseMap::default()
+        };
+        Some(SortedMemory {
+            config: &self.config,
+            interner,
+            words,
+            casings: &self.casings,
+            reverse,
+            lengths: self.lengths.map_ids(|start| new_ids[start as usize]),
+        })
+    }
+
+    /// Loads a markov chain that was saved with `Memory::save_raw` from the given reader. Returns `Error::InvalidOrder` if the data has an order of 0, and `Error::UnknownWordId` if it refers to a word it doesn't contain, as corrupt data would otherwise panic later.
+    pub fn load_raw<R: Read>(reader: R) -> Result<Memory, Error> {
+        let mut memory: Memory =
+            bincode::deserialize_from(reader).map_err(Error::CouldNotDeserialize)?;
+        memory.check_order_valid()?;
+        memory.check_word_ids()?;
+        memory.recount_totals();
+        Ok(memory)
+    }
+
+    /// Export this chain as human-readable JSON. The word combinations are written as a list of `[context, [[part, count], ...]]` entries, with every word written out in full, e.g. `[["StartOfLine", {"Word": "hello"}], [[{"Word": "world"}, 1]]]`.
+    pub fn export_json<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, &JsonMemory::new(self)).map_err(Error::Json)
+    }
+
+    /// Import a chain from JSON that was written by `Memory::export_json`, or edited by hand. Word combinations that are listed twice have their counts added together. Returns `Error::InvalidOrder` if the JSON has an order of 0.
+    pub fn import_json<R: Read>(reader: R) -> Result<Memory, Error> {
+        let json: JsonMemory = serde_json::from_reader(reader).map_err(Error::Json)?;
+        let mut memory = json.into_memory()?;
+        memory.rebuild_reverse();
+        Ok(memory)
+    }
+
+    /// Write this chain as a Graphviz DOT graph, to look at it as a picture. Every context is drawn as a node, with an edge to every context that followed it, labeled with how often it did. The start and the end of a line are drawn as separately shaped nodes.
+    ///
+    /// The graph of a large chain is unreadable, so `options` can leave out rare transitions and all but the most common contexts.
+    pub fn to_dot<W: Write>(&self, writer: W, options: &DotOptions) -> std::io::Result<()> {
+        dot::write_dot(self, writer, options)
+    }
+
+    /// Check that the order of this chain is at least 1 after deserializing, like `Memory::with_order` requires, so a corrupt file is rejected instead of panicking once a line is learned. Returns `Error::InvalidOrder` otherwise.
+    fn check_order_valid(&self) -> Result<(), Error> {
+        match self.config.order {
+            0 => Err(Error::InvalidOrder(0)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that every word id of this chain was handed out by its interner after deserializing, so a corrupt file is rejected instead of panicking once the word is looked up. Returns `Error::UnknownWordId` otherwise.
+    fn check_word_ids(&self) -> Result<(), Error> {
+        let words = self.interner.len();
+        let mut ids = self
+            .words
+            .iter()
+            .flat_map(|(context, next)| context.word_ids().chain(next.word_ids()))
+            .chain(self.reverse.word_ids())
+            .chain(self.lengths.starts());
+        match ids.find(|&id| id as usize >= words) {
+            Some(id) => Err(Error::UnknownWordId { id, words }),
+            None => Ok(()),
+        }
+    }
+
+    /// Rebuild the reverse map of a bidirectional chain after its contexts changed in a way that can't be followed count by count, e.g. by pruning
+    fn rebuild_reverse(&mut self) {
+        if self.config.bidirectional {
+            self.reverse = ReverseMap::build(&self.words);
+        }
+    }
+
+    /// Forget the words that are no longer part of any context after contexts were removed or merged, e.g. by pruning, and renumber the rest, so the interner doesn't keep growing. The lengths of lines are only kept for words that still start a line, and the reverse map is rebuilt.
+    fn compact(&mut self) {
+        let mut used = vec![false; self.interner.len()];
+        let mut starts = vec![false; self.interner.len()];
+        for (context, next) in &self.words {
+            let parts = context
+                .parts()
+                .iter()
+                .chain(next.iter().map(|(part, _)| part));
+            for part in parts {
+                if let Part::Word(word) = *part {
+                    used[word as usize] = true;
+                }
+            }
+            if let Some(word) = context.start_word() {
+                starts[word as usize] = true;
+            }
+        }
+
+        let new_ids = self.interner.compact(|id| used[id as usize]);
+        let renumber = |part| match part {
+            Part::Word(word) => Part::Word(new_ids[word as usize].expect("used words are kept")),
+            part => part,
+        };
+        self.words = std::mem::take(&mut self.words)
+            .into_iter()
+            .map(|(context, next)| (context.map_parts(renumber), next.map_parts(renumber)))
+            .collect();
+        self.lengths
+            .remap(|start| new_ids[start as usize].filter(|_| starts[start as usize]));
+        let interner = &self.interner;
+        self.casings.retain(|word| interner.id(word).is_some());
+        self.rebuild_reverse();
+    }
+
+    /// Restore the totals of every `NextPartList` after deserializing, as they are not saved
+    fn recount_totals(&mut self) {
+        for next in self.words.values_mut() {
+            next.recount_total();
+        }
+        self.reverse.recount_totals();
+    }
+
     /// Learn the given line. This will append the word combinations to the internal memory model.
     pub fn learn(&mut self, line: &str) {
-        // We split the line into chunks:
-        // - __START__ + first word
-        // - first word + second word
-        // - ...
-        // - last_word + __END__
-        let mut previous_pair = SentencePartPair::default();
-
-        let add_sequence = |memory: &mut Self, prev_pair, part| {
-            memory
-                .words
-                .entry(prev_pair)
-                .or_insert_with(NextPartList::default)
-                .count_part(part);
-        };
+        self.learn_weighted(line, 1);
+    }
 
-        for part in line
-            .split_ascii_whitespace()
-            .filter(|part| !part.trim().is_empty())
-        {
-            if previous_pair.is_valid_sentence() {
-                // if the `previous` is a valid word segment, we add the current word to the list of follow-up words.
-                let new_word = SentencePart::Word(part.to_owned());
-                add_sequence(self, previous_pair.clone(), new_word);
+    /// Learn every sentence of the given `text` as a line of its own, see `Memory::learn`. This way a paragraph isn't learned as one long sentence.
+    ///
+    /// A sentence ends after a `.`, `!` or `?` that is followed by whitespace, or at the end of the text. This is a simple heuristic, abbreviations like "e.g." end a sentence too.
+    pub fn learn_text(&mut self, text: &str) {
+        for sentence in tokenizer::sentences(text) {
+            self.learn(sentence);
+        }
+    }
+
+    /// Learn the given line as if it was learned `weight` times, see `Memory::learn`. A `weight` of 0 does nothing.
+    pub fn learn_weighted(&mut self, line: &str, weight: usize) {
+        self.learn_counted(line, weight);
+    }
+
+    /// Learn the given line, like `Memory::learn`, and report how much of it was new to this chain. A line that only adds to existing counts teaches the chain nothing new.
+    pub fn learn_reporting(&mut self, line: &str) -> LearnStats {
+        self.learn_counted(line, 1)
+    }
+
+    /// Learn a line that was already split into the given `tokens`, e.g. by a custom NLP pipeline, like `Memory::learn` learns a line. The tokens are learned as they are, without splitting or normalizing them, so they should be normalized the way this chain looks up words, e.g. lowercased for the default normalization.
+    pub fn learn_tokens<I: IntoIterator<Item = String>>(&mut self, tokens: I) {
+        let tokens: Vec<String> = tokens.into_iter().collect();
+        let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        self.learn_words(&tokens, 1, false);
+    }
+
+    /// Learn the given line `weight` times, and count what was new
+    fn learn_counted(&mut self, line: &str, weight: usize) -> LearnStats {
+        let tokens = self.tokens(line);
+        self.learn_words(&tokens, weight, true)
+    }
+
+    /// Learn the given words of a line `weight` times, and count what was new. If `normalize` is false, the words are learned as they are
+    fn learn_words(&mut self, tokens: &[&str], weight: usize, normalize: bool) -> LearnStats {
+        let mut stats = LearnStats::default();
+        if weight == 0 {
+            return stats;
+        }
+        stats.tokens = tokens.len();
+        let line_words = match self.line_ids(tokens, normalize, true) {
+            Some(line_words) => line_words,
+            None => return stats,
+        };
+        let words = &mut self.words;
+        let reverse = &mut self.reverse;
+        let bidirectional = self.config.bidirectional;
+        SentenceContext::for_each_transition(self.config.order, &line_words, |context, part| {
+            // Only clone the context if it's new
+            let is_new = match words.get_mut(context) {
+                Some(next) => {
+                    let is_new = next.count(&part) == 0;
+                    next.count_part_n(part, weight);
+                    is_new
+                }
+                None => {
+                    let mut next = NextPartList::default();
+                    next.count_part_n(part, weight);
+                    words.insert(context.clone(), next);
+                    stats.new_contexts += 1;
+                    true
+                }
+            };
+            if is_new {
+                stats.new_transitions += 1;
+            }
+            if bidirectional {
+                reverse.count(context, part, weight, is_new);
             }
-            previous_pair.shift(part);
+        });
+        if let Some((start, len)) = Self::line_length(&line_words) {
+            self.lengths.count(start, len, weight);
         }
-        // this should always be true, unless the caller provides an empty string
-        if previous_pair.is_valid_sentence() {
-            add_sequence(self, previous_pair, SentencePart::EndOfLine);
+        if normalize && self.config.display_casing {
+            for word in tokens {
+                let normalized = self.normalize(word);
+                if !self.config.banlist.contains(&*normalized) {
+                    self.casings.count(&normalized, word);
+                }
+            }
         }
+        stats
     }
 
-    /// Tries to produce a sentence starting with the given `starting_word`.
+    /// Learn every line of the given reader, see `Memory::learn`. Returns the amount of lines that were learned.
     ///
-    /// No validation is given to the word, if the starting word is not a valid word (e.g. it's multiple words), this function will always return None.
-    pub fn speak(&self, starting_word: &str) -> Option<String> {
-        let mut len = 0;
-        let mut rand = rand::thread_rng();
-        let mut result = String::new();
-
-        // We always start with __START__, starting_word
-        let mut previous_pair =
-            SentencePartPair::with_previous_word(starting_word.to_ascii_lowercase());
-
-        // While the combination of the last 2 words is known
-        while let Some(words) = self.words.get(&previous_pair) {
-            // Try to get a random follow-up word
-            let word = match words.get(&mut rand) {
-                Some(SentencePart::Word(next_word)) => next_word,
-                _ => break,
+    /// Lines that aren't valid UTF-8 are skipped, as logs often contain some. Stops at the first other IO error, and returns that error. Lines read before the error are still learned.
+    pub fn learn_reader<R: BufRead>(&mut self, reader: R) -> std::io::Result<usize> {
+        self.learn_reader_with(reader, |line| Some(line))
+    }
+
+    /// Learn every line of the given reader, like `Memory::learn_reader`, but only the text that `extractor` returns for the line. Lines for which `extractor` returns None are skipped. Returns the amount of lines that were learned.
+    ///
+    /// This is useful for chat logs, where every line starts with a timestamp or a nick, e.g. `|line| line.split('>').nth(1)` for lines like `<nick> message`.
+    pub fn learn_reader_with<R, F>(&mut self, reader: R, mut extractor: F) -> std::io::Result<usize>
+    where
+        R: BufRead,
+        F: FnMut(&str) -> Option<&str>,
+    {
+        let mut count = 0;
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+                Err(e) => return Err(e),
             };
+            if let Some(text) = extractor(&line) {
+                self.learn(text);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Create a chain with the default settings that learned every given line, see `Memory::learn`, spreading the work over multiple threads. Use `Memory::learn_par_into` to learn with other settings, or into a chain that already learned something.
+    #[cfg(feature = "rayon")]
+    pub fn learn_par<I>(lines: I) -> Memory
+    where
+        I: IntoParallelIterator<Item = String>,
+    {
+        let mut memory = Memory::default();
+        memory.learn_par_into(lines);
+        memory
+    }
+
+    /// Learn every given line into this chain, see `Memory::learn`, spreading the work over multiple threads.
+    ///
+    /// The lines are split into one chunk per thread, every thread learns its chunk into a separate chain, and these are merged together with `Memory::merge` afterwards.
+    #[cfg(feature = "rayon")]
+    pub fn learn_par_into<I>(&mut self, lines: I)
+    where
+        I: IntoParallelIterator<Item = String>,
+    {
+        let lines: Vec<String> = lines.into_par_iter().collect();
+        // Merging a chain costs about as much as learning its lines, so every thread only learns a single chain
+        let threads = rayon::current_num_threads();
+        let chunk_len = lines.len().div_ceil(threads).max(1);
+        let config = &self.config;
+        let split = &self.split;
+        let learned = lines
+            .par_chunks(chunk_len)
+            .map(|chunk| {
+                let mut memory = Memory {
+                    split: split.clone(),
+                    ..Memory::with_config(config.clone())
+                };
+                for line in chunk {
+                    memory.learn(line);
+                }
+                memory
+            })
+            .reduce(
+                || Memory::with_config(config.clone()),
+                |memory, other| {
+                    // Merging copies every context of the merged chain, so the smaller chain is merged into the bigger one
+                    let (mut bigger, smaller) = if memory.words.len() >= other.words.len() {
+                        (memory, other)
+                    } else {
+                        (other, memory)
+                    };
+                    bigger.merge(smaller);
+                    bigger
+                },
+            );
+        if self.words.is_empty() && self.interner.len() == 0 {
+            // There's nothing to merge into, so the learned chain is taken as it is
+            self.interner = learned.interner;
+            self.words = learned.words;
+            self.casings = learned.casings;
+            self.reverse = learned.reverse;
+            self.lengths = learned.lengths;
+        } else {
+            self.merge(learned);
+        }
+    }
 
-            if !result.is_empty() {
-                result += " ";
+    /// Unlearn the given line. This walks the same word combinations as `Memory::learn`, but removes one count for each of them instead. Word combinations that were never learned are left untouched.
+    ///
+    /// This only perfectly reverses `Memory::learn` if nothing else was learned that contains the same word combinations.
+    pub fn unlearn(&mut self, line: &str) {
+        let tokens = self.tokens(line);
+        let line_words = match self.line_ids(&tokens, true, false) {
+            Some(line_words) => line_words,
+            None => return,
+        };
+        let words = &mut self.words;
+        let reverse = &mut self.reverse;
+        let bidirectional = self.config.bidirectional;
+        SentenceContext::for_each_transition(self.config.order, &line_words, |context, part| {
+            if let Some(next) = words.get_mut(context) {
+                if next.count(&part) == 0 {
+                    return;
+                }
+                next.uncount_part(&part);
+                if bidirectional {
+                    reverse.uncount(context, part, next.count(&part) == 0);
+                }
+                if next.is_empty() {
+                    words.remove(context);
+                }
             }
-            result += word;
-            previous_pair.shift(word);
+        });
+        if let Some((start, len)) = Self::line_length(&line_words) {
+            self.lengths.uncount(start, len);
+        }
+        if self.config.display_casing {
+            for word in tokens {
+                let normalized = self.normalize(word);
+                self.casings.uncount(&normalized, word);
+            }
+        }
+    }
 
-            len += 1;
+    /// Apply the maximum token length to the given word, see `Memory::set_max_token_len`. Returns None if the word should be dropped.
+    fn limit_token_len<'a>(&self, word: &'a str) -> Option<&'a str> {
+        match self.config.max_token_len {
+            Some(max_token_len) => self.config.long_tokens.limit(word, max_token_len),
+            None => Some(word),
+        }
+    }
 
-            // We don't want to get in an infinite loop,
-            // so we add 10% chance to break at the current word, for each 3 words we added
-            let chance_to_break = (len / 3) * 10;
-            if rand.gen_bool(chance_to_break as f64 / 100.0) {
-                break;
+    /// Split the given line into the words that are learned
+    fn tokens<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        let tokens = match &self.split {
+            Some(split) => split.tokenize(line),
+            None => self.config.tokenizer.tokenize(line),
+        };
+        tokens
+            .into_iter()
+            .filter_map(|part| self.limit_token_len(part))
+            // Truncating can leave nothing of a word, e.g. with a maximum length of 0
+            .filter(|part| !part.trim().is_empty())
+            .collect()
+    }
+
+    /// Get the id of every given word of a line, after normalizing it if `normalize` is true, and interning it if `intern` is true. Banned words and words without an id are None, so the word combinations that contain them are skipped, see `Memory::set_banlist`
+    ///
+    /// Returns None if the line is too short to be learned, see `Memory::set_min_tokens_to_learn`. Learning and unlearning both go through this, so they always walk the same word combinations.
+    fn line_ids(
+        &mut self,
+        tokens: &[&str],
+        normalize: bool,
+        intern: bool,
+    ) -> Option<Vec<Option<WordId>>> {
+        if tokens.len() < self.config.min_tokens_to_learn {
+            return None;
+        }
+        let mut result = Vec::with_capacity(tokens.len());
+        for &word in tokens {
+            let word = if normalize {
+                self.normalize(word)
+            } else {
+                Cow::Borrowed(word)
+            };
+            if self.config.banlist.contains(&*word) {
+                result.push(None);
+            } else if intern {
+                result.push(Some(self.interner.intern(&word)));
+            } else {
+                result.push(self.interner.id(&word));
             }
         }
+        Some(result)
+    }
 
-        if result.is_empty() {
-            None
-        } else {
-            // Make sure to prepend the requested `starting_word`
-            Some(format!("{} {}", starting_word.to_ascii_lowercase(), result))
+    /// Get the word that started the line of the given word ids, and how many words followed it, as they are counted towards the lengths of lines. Returns None if the start of the line isn't learned, because its first or second word is banned. Banned words don't count towards the length.
+    fn line_length(line_words: &[Option<WordId>]) -> Option<(WordId, usize)> {
+        let start = match line_words {
+            [Some(start)] | [Some(start), Some(_), ..] => *start,
+            _ => return None,
+        };
+        Some((start, line_words.iter().flatten().count() - 1))
+    }
+
+    /// Checks if the word with the given id is banned, see `Memory::set_banlist`
+    pub(crate) fn is_banned(&self, word: WordId) -> bool {
+        !self.config.banlist.is_empty() && self.config.banlist.contains(self.word(word))
+    }
+
+    /// Merge the given chain into this chain. The counts of every word combination the chains have in common are added together, and all other word combinations are copied over.
+    ///
+    /// Both chains should have the same order, use `Memory::merge_checked` to make sure they do. The contexts of chains with different orders have different lengths, so e.g. merging a chain with an order of 2 into one with an order of 3 adds contexts that are never looked up, and isn't meaningful.
+    pub fn merge(&mut self, other: Memory) {
+        self.merge_ref(&other);
+    }
+
+    /// Merge the given chain into this chain, like `Memory::merge`, but returns `Error::OrderMismatch` without merging anything if the chains have different orders.
+    pub fn merge_checked(&mut self, other: Memory) -> Result<(), Error> {
+        self.check_order(&other)?;
+        self.merge_ref(&other);
+        Ok(())
+    }
+
+    /// Returns `Error::OrderMismatch` if `other` has a different order than this chain
+    fn check_order(&self, other: &Memory) -> Result<(), Error> {
+        if other.config.order != self.config.order {
+            return Err(Error::OrderMismatch {
+                expected: self.config.order,
+                found: other.config.order,
+            });
+        }
+        Ok(())
+    }
+
+    /// Merge the given chain into this chain, like `Memory::merge`, but without taking ownership of `other`.
+    pub fn merge_ref(&mut self, other: &Memory) {
+        // The chains give different ids to the same words
+        let ids: Vec<WordId> = other
+            .interner
+            .words()
+            .map(|word| self.interner.intern(word))
+            .collect();
+        let to_own_id = |part| match part {
+            Part::Word(id) => Part::Word(ids[id as usize]),
+            part => part,
+        };
+        // Contexts both chains know are looked up with this buffer, so only new contexts allocate
+        let mut parts = Vec::with_capacity(self.config.order);
+        for (context, next) in &other.words {
+            parts.clear();
+            parts.extend(context.parts().iter().map(|&part| to_own_id(part)));
+            if self.config.bidirectional {
+                let context = SentenceContext::from_parts(parts.clone());
+                let own = self.words.get(&context);
+                for (&part, count) in next.iter() {
+                    let part = to_own_id(part);
+                    let is_new = own.is_none_or(|own| own.count(&part) == 0);
+                    self.reverse.count(&context, part, count, is_new);
+                }
+            }
+            match self.words.get_mut(&parts[..]) {
+                Some(own) => {
+                    for (&part, count) in next.iter() {
+                        own.count_part_n(to_own_id(part), count);
+                    }
+                }
+                None => {
+                    let context = SentenceContext::from_parts(parts.clone());
+                    self.words.insert(context, next.map_parts(to_own_id));
+                }
+            }
+        }
+        self.casings.merge(&other.casings);
+        self.lengths.merge(&other.lengths, |id| ids[id as usize]);
+    }
+
+    /// Save only what this chain learned since it was the `baseline` chain, e.g. the last saved copy of itself, as plain bincode like `Memory::save_raw`. Saving this delta is much faster than saving the whole chain when little was learned in between. Use `Memory::apply_delta` to add the delta to the `baseline` chain again.
+    ///
+    /// Only counts that grew are saved, so lines that were unlearned or pruned since the `baseline` are still in the `baseline` after applying the delta.
+    pub fn save_delta<W: Write>(&self, baseline: &Memory, writer: W) -> Result<(), Error> {
+        // The delta is only merged into another chain, so it doesn't need a reverse map of its own
+        let mut delta = Memory::with_config(Config {
+            bidirectional: false,
+            ..self.config.clone()
+        });
+        for (context, next) in &self.words {
+            // The chains give different ids to the same words
+            let baseline_context = context
+                .parts()
+                .iter()
+                .map(|&part| self.translate(part, baseline))
+                .collect::<Option<Vec<_>>>()
+                .and_then(|context| baseline.words.get(&context[..]));
+            let mut delta_next = NextPartList::default();
+            for (&part, count) in next.iter() {
+                let baseline_count = baseline_context
+                    .and_then(|baseline_next| {
+                        Some(baseline_next.count(&self.translate(part, baseline)?))
+                    })
+                    .unwrap_or(0);
+                if count > baseline_count {
+                    delta_next
+                        .count_part_n(self.copy_part(part, &mut delta), count - baseline_count);
+                }
+            }
+            if !delta_next.is_empty() {
+                let context = context.map_parts(|part| self.copy_part(part, &mut delta));
+                delta.words.insert(context, delta_next);
+            }
+        }
+        delta.casings = self.casings.growth_since(&baseline.casings);
+        delta.lengths = self
+            .lengths
+            .growth_since(&baseline.lengths, |id| baseline.interner.id(self.word(id)))
+            .map_ids(|id| delta.interner.intern(self.word(id)));
+        delta.save_raw(writer)
+    }
+
+    /// Add a delta that was saved with `Memory::save_delta` to this chain. This chain should be the baseline the delta was saved against, or a copy of it.
+    ///
+    /// Returns `Error::OrderMismatch` if the delta was saved by a chain with a different order.
+    pub fn apply_delta<R: Read>(&mut self, reader: R) -> Result<(), Error> {
+        let delta = Memory::load_raw(reader)?;
+        self.check_order(&delta)?;
+        self.merge_ref(&delta);
+        Ok(())
+    }
+
+    /// Get the part of `other` that has the same word as the given part of this chain. Returns None if `other` never learned the word.
+    fn translate(&self, part: Part, other: &Memory) -> Option<Part> {
+        match part {
+            Part::Word(id) => other.interner.id(self.word(id)).map(Part::Word),
+            part => Some(part),
+        }
+    }
+
+    /// Get the part of `other` that has the same word as the given part of this chain, interning the word in `other` if necessary
+    fn copy_part(&self, part: Part, other: &mut Memory) -> Part {
+        match part {
+            Part::Word(id) => Part::Word(other.interner.intern(self.word(id))),
+            part => part,
+        }
+    }
+
+    /// The amount of contexts this chain has learned.
+    pub fn state_count(&self) -> usize {
+        self.words.len()
+    }
+
+    /// The amount of distinct word combinations this chain has learned, i.e. the sum of the distinct follow-up parts of every context.
+    pub fn transition_count(&self) -> usize {
+        self.words.values().map(NextPartList::len).sum()
+    }
+
+    /// The amount of distinct words this chain has learned.
+    pub fn vocabulary(&self) -> usize {
+        let mut words = HashSet::new();
+        for (context, next) in &self.words {
+            let parts = context
+                .parts()
+                .iter()
+                .chain(next.iter().map(|(part, _)| part));
+            words.extend(parts.filter_map(|part| match *part {
+                Part::Word(word) => Some(word),
+                _ => None,
+            }));
+        }
+        words.len()
+    }
+
+    /// Estimate the amount of bytes this chain uses, including everything it has allocated on the heap. This is not exact, but it is useful to keep a chain within a memory budget, e.g. with `Memory::prune`.
+    pub fn estimated_size_bytes(&self) -> usize {
+        std::mem::size_of::<Memory>()
+            + map_heap_size(&self.words)
+            + self
+                .words
+                .iter()
+                .map(|(context, next)| context.heap_size() + next.heap_size())
+                .sum::<usize>()
+            + self.interner.heap_size()
+            + self.casings.heap_size()
+            + self.reverse.heap_size()
+            + self.lengths.heap_size()
+    }
+
+    /// Get the id this chain gives to the given word, after lowercasing it like `Memory::learn` does. Returns None if the chain never learned the word.
+    ///
+    /// Every learned word has a small, unique id, starting at 0. Ids stay the same while the chain exists, and are saved with it, so they can be used to analyze a chain compactly, e.g. to build a co-occurrence matrix. Words keep their id even after they were unlearned or pruned, until `Memory::clear` is called.
+    pub fn word_id(&self, word: &str) -> Option<u32> {
+        self.id(word)
+    }
+
+    /// Get the word with the given id, see `Memory::word_id`. Returns None if no word has this id.
+    pub fn word_for_id(&self, id: u32) -> Option<&str> {
+        self.interner.get(id)
+    }
+
+    /// Iterate over every context this chain has learned. Each context contains the last `order` parts of a sentence, oldest first, e.g. `[StartOfLine, Word("hello")]` for a line starting with "hello" in a chain with an order of 2.
+    pub fn contexts(&self) -> impl Iterator<Item = Vec<SentencePart>> + '_ {
+        self.words.keys().map(move |context| {
+            context
+                .parts()
+                .iter()
+                .map(|part| self.resolve(*part))
+                .collect()
+        })
+    }
+
+    /// Every word a sentence starting with the given `starting_word` can contain, including the starting word itself, found by following every word combination this chain has learned. The words are normalized, like `Memory::learn` stores them. Returns an empty set if no sentence can start with the word.
+    ///
+    /// This walks every context that is reachable from the start, so it can take a while for large chains.
+    pub fn reachable_words(&self, starting_word: &str) -> HashSet<String> {
+        let start = self
+            .starting_word(starting_word)
+            .ok()
+            .and_then(|word| self.interner.id(&word))
+            .map(|word| SentenceContext::with_previous_word(self.config.order, word));
+        let mut queue: VecDeque<_> = start.into_iter().collect();
+        let mut visited: HashSet<_> = queue.iter().cloned().collect();
+        let mut words = HashSet::new();
+        let mut new_lines = false;
+        while let Some(context) = queue.pop_front() {
+            let next = match self.words.get(&context) {
+                Some(next) => next,
+                None => continue,
+            };
+            words.extend(context.parts().iter().filter_map(|part| match *part {
+                Part::Word(word) => Some(word),
+                _ => None,
+            }));
+            for (&part, _) in next.iter() {
+                let next_contexts = match part {
+                    // A new line starts like one of the learned lines, which only need to be queued once
+                    Part::EndOfLine if self.config.continue_across_lines && !new_lines => {
+                        new_lines = true;
+                        self.words
+                            .keys()
+                            .filter(|context| context.start_word().is_some())
+                            .cloned()
+                            .collect()
+                    }
+                    Part::EndOfLine | Part::StartOfLine => Vec::new(),
+                    _ => {
+                        let mut next_context = context.clone();
+                        next_context.shift_part(part);
+                        vec![next_context]
+                    }
+                };
+                for next_context in next_contexts {
+                    if visited.insert(next_context.clone()) {
+                        queue.push_back(next_context);
+                    }
+                }
+            }
+        }
+        words
+            .into_iter()
+            .map(|word| self.word(word).to_owned())
+            .collect()
+    }
+
+    /// Every context from which no sentence can end naturally, because none of the word combinations that follow it ever reaches the end of a line. Sentences that run into such a context can only end by a random break, or when they get too long. The contexts are in no particular order, and look like the contexts of `Memory::contexts`.
+    ///
+    /// This walks the entire chain backwards, starting from the contexts that ended a line, so it can take a while for large chains.
+    pub fn dead_end_contexts(&self) -> Vec<Vec<SentencePart>> {
+        let mut previous = HashMap::<SentenceContext, Vec<&SentenceContext>>::new();
+        let mut queue = VecDeque::new();
+        let mut can_end = HashSet::new();
+        for (context, next) in &self.words {
+            for (&part, _) in next.iter() {
+                match part {
+                    Part::EndOfLine => {
+                        if can_end.insert(context) {
+                            queue.push_back(context);
+                        }
+                    }
+                    Part::StartOfLine => {}
+                    _ => {
+                        let mut next_context = context.clone();
+                        next_context.shift_part(part);
+                        previous.entry(next_context).or_default().push(context);
+                    }
+                }
+            }
+        }
+
+        while let Some(context) = queue.pop_front() {
+            for &previous_context in previous.get(context).into_iter().flatten() {
+                if can_end.insert(previous_context) {
+                    queue.push_back(previous_context);
+                }
+            }
+        }
+
+        self.words
+            .keys()
+            .filter(|context| !can_end.contains(context))
+            .map(|context| {
+                context
+                    .parts()
+                    .iter()
+                    .map(|part| self.resolve(*part))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Check that this chain is intact, e.g. after loading a file that may be corrupt or edited by hand. The order must be at least 1, every context must have as many parts as the order of the chain, may only start a line at its oldest parts, must end in a word and must be followed by at least one part, and every part must be counted at least once and use a known word. The errors are in no particular order.
+    ///
+    /// A chain that breaks these invariants doesn't make generation fail, but it can generate nonsense or stop early, so servers can call this to reject bad files.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        if self.config.order == 0 {
+            errors.push(ValidationError::InvalidOrder(self.config.order));
+        }
+        let mut unknown_ids = BTreeSet::new();
+        let mut resolve = |part: Part| match part {
+            Part::Word(id) if self.interner.get(id).is_none() => {
+                unknown_ids.insert(id);
+                SentencePart::Unknown
+            }
+            part => self.resolve(part),
+        };
+        for (context, next) in &self.words {
+            let parts = context.parts();
+            let resolved: Vec<_> = parts.iter().map(|&part| resolve(part)).collect();
+            if parts.len() != self.config.order {
+                errors.push(ValidationError::WrongContextLength {
+                    context: resolved.clone(),
+                    expected: self.config.order,
+                });
+            }
+            let words = &parts[parts
+                .iter()
+                .take_while(|&&part| part == Part::StartOfLine)
+                .count()..];
+            if words.is_empty()
+                || !words
+                    .iter()
+                    .all(|&part| matches!(part, Part::Word(_) | Part::Unknown))
+            {
+                errors.push(ValidationError::MisplacedSentinel {
+                    context: resolved.clone(),
+                });
+            }
+            if next.is_empty() {
+                errors.push(ValidationError::NoFollowUps {
+                    context: resolved.clone(),
+                });
+            }
+            for (&part, count) in next.iter() {
+                if part == Part::StartOfLine {
+                    errors.push(ValidationError::StartAfterContext {
+                        context: resolved.clone(),
+                    });
+                }
+                let part = resolve(part);
+                if count == 0 {
+                    errors.push(ValidationError::ZeroCount {
+                        context: resolved.clone(),
+                        part,
+                    });
+                }
+            }
+        }
+        errors.extend(unknown_ids.into_iter().map(ValidationError::UnknownWordId));
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Consume this chain and iterate over every context it has learned, like `Memory::contexts`, together with the parts that followed the context and how often they did. This is useful to move a chain into another storage, e.g. a database.
+    ///
+    /// The contexts are moved out of the chain one by one, but every word is stored only once in the chain, so the words of every entry are copied.
+    pub fn into_entries(
+        self,
+    ) -> impl Iterator<Item = (Vec<SentencePart>, Vec<(SentencePart, usize)>)> {
+        let Memory {
+            interner, words, ..
+        } = self;
+        let resolve = move |part: Part| match part {
+            Part::StartOfLine => SentencePart::StartOfLine,
+            Part::EndOfLine => SentencePart::EndOfLine,
+            Part::Word(id) => SentencePart::Word(interner.word(id).to_owned()),
+            Part::Unknown => SentencePart::Unknown,
+        };
+        words.into_iter().map(move |(context, next)| {
+            let context = context.parts().iter().map(|&part| resolve(part)).collect();
+            let next = next
+                .iter()
+                .map(|(&part, count)| (resolve(part), count))
+                .collect();
+            (context, next)
+        })
+    }
+
+    /// The parts that followed the given `context`, with how often they did, as they are stored in this chain. Returns None if the context was never learned.
+    ///
+    /// Only available with the `unstable-internals` feature, see `markov::internals`.
+    #[cfg(feature = "unstable-internals")]
+    pub fn next_parts(&self, context: &SentenceContext) -> Option<&NextPartList> {
+        self.words.get(context)
+    }
+
+    /// Iterate over every context this chain has learned, together with the parts that followed it, as they are stored in this chain. Use `Memory::word_for_id` to get the words of the parts.
+    ///
+    /// Only available with the `unstable-internals` feature, see `markov::internals`.
+    #[cfg(feature = "unstable-internals")]
+    pub fn raw_entries(&self) -> impl Iterator<Item = (&SentenceContext, &NextPartList)> {
+        self.words.iter()
+    }
+
+    /// The probability, between 0.0 and 1.0, that `next` follows the given `context` words.
+    ///
+    /// The `context` words are given oldest first. If fewer words than the order of this chain are given, the context is assumed to be at the start of a line, e.g. `&["hello"]` is the context of a line starting with "hello". If more words are given, only the last ones are used.
+    ///
+    /// Returns None if the context is unknown, and `Some(0.0)` if the context is known, but was never followed by `next`.
+    pub fn transition_probability(&self, context: &[&str], next: &str) -> Option<f64> {
+        let next_parts = self.next_parts_for(context)?;
+        let next = match self.id(next) {
+            Some(next) => Part::Word(next),
+            None => return Some(0.0),
+        };
+        Some(next_parts.probability(&next))
+    }
+
+    /// Pick a random part to follow the given `context` words, weighed by how often each part followed them. The returned part is owned, so it doesn't keep this memory borrowed, which helps when building other generators on top of this crate.
+    ///
+    /// See `Memory::transition_probability` for how the `context` words are interpreted. Returns None if the context is unknown.
+    pub fn sample_next<R: Rng>(&self, context: &[&str], rng: &mut R) -> Option<SentencePart> {
+        let next = self.next_parts_for(context)?;
+        next.get_owned(rng).map(|part| self.resolve(part))
+    }
+
+    /// Pick a random part to follow the given `context` words, like `Memory::sample_next`, but weighed by the weight `f` returns for every part and how often it followed the context. See `NextPartList::get_weighted_by`.
+    ///
+    /// Parts with a weight that isn't positive are never picked. Returns None if the context is unknown, or no part has a positive weight.
+    pub fn sample_next_weighted_by<R: Rng, F: Fn(&SentencePart, usize) -> f64>(
+        &self,
+        context: &[&str],
+        rng: &mut R,
+        f: F,
+    ) -> Option<SentencePart> {
+        let next = self.next_parts_for(context)?;
+        next.get_weighted_by(rng, |part, count| f(&self.resolve(*part), count))
+            .map(|part| self.resolve(*part))
+    }
+
+    /// How surprised this chain is by the given `line`, i.e. the perplexity of the line. Lower is more likely, and a line that the chain always produces has a perplexity of 1.0. This is useful to rank lines by how well they fit the learned lines, e.g. to filter spam.
+    ///
+    /// The line is split into word combinations like `Memory::learn` does. Word combinations that were never learned would make the perplexity infinite, so every count is smoothed by adding `smoothing` to the count of every possible next part, i.e. every known word and the end of the line. A `smoothing` of 0 disables this.
+    ///
+    /// Returns None if the line contains no words.
+    pub fn perplexity(&self, line: &str, smoothing: f64) -> Option<f64> {
+        let line_words: Vec<_> = self
+            .tokens(line)
+            .into_iter()
+            .map(|word| self.id(word))
+            .collect();
+        if line_words.is_empty() {
+            return None;
+        }
+        // Every word, and the end of the line
+        let possible_parts = (self.interner.len() + 1) as f64;
+        // Without smoothing, unknown word combinations have a probability of 0, which makes the perplexity infinite
+        let smoothed = |count: usize, total: usize| {
+            let total = total as f64 + smoothing * possible_parts;
+            if total > 0.0 {
+                (count as f64 + smoothing) / total
+            } else {
+                0.0
+            }
+        };
+
+        let mut log_probability = 0.0;
+        let mut known_steps = 0;
+        SentenceContext::for_each_transition(self.config.order, &line_words, |context, part| {
+            known_steps += 1;
+            let probability = match self.words.get(context) {
+                Some(next) => smoothed(next.count(&part), next.total()),
+                None => smoothed(0, 0),
+            };
+            log_probability += probability.ln();
+        });
+        // Every word but the first is a step, and so is the end of the line. Steps with unknown words are skipped above, but they still have a chance
+        let steps = line_words.len();
+        if known_steps < steps {
+            log_probability += (steps - known_steps) as f64 * smoothed(0, 0).ln();
+        }
+
+        Some((-log_probability / steps as f64).exp())
+    }
+
+    /// If this chain was created with a maximum vocabulary, see `Memory::with_max_vocabulary`, replace every word outside of the most frequent words with `SentencePart::Unknown`. Word combinations that become the same have their counts added together.
+    ///
+    /// Generated sentences never contain unknown words, another word is picked instead where possible. Words that are learned afterwards are kept until this is called again.
+    ///
+    /// The replaced words are forgotten, together with their casings and the lengths of the lines they started, see `Memory::expected_length`. Returns the amount of distinct words that were replaced.
+    pub fn limit_vocabulary(&mut self) -> usize {
+        let max_vocabulary = match self.config.max_vocabulary {
+            Some(max_vocabulary) => max_vocabulary,
+            None => return 0,
+        };
+
+        // Every word either started a line, or followed another part
+        let mut frequencies = HashMap::<WordId, usize>::new();
+        for (context, next) in &self.words {
+            if let Some(word) = context.start_word() {
+                *frequencies.entry(word).or_insert(0) += next.total();
+            }
+            for (part, count) in next.iter() {
+                if let Part::Word(word) = *part {
+                    *frequencies.entry(word).or_insert(0) += count;
+                }
+            }
+        }
+        if frequencies.len() <= max_vocabulary {
+            return 0;
+        }
+        let removed = frequencies.len() - max_vocabulary;
+        let known: HashSet<WordId> =
+            most_counted(frequencies.into_iter().collect(), max_vocabulary)
+                .into_iter()
+                .map(|(word, _)| word)
+                .collect();
+
+        let forget_unknown = |part| match part {
+            Part::Word(word) if !known.contains(&word) => Part::Unknown,
+            part => part,
+        };
+        for (context, next) in std::mem::take(&mut self.words) {
+            let context = context.map_parts(forget_unknown);
+            let next = next.map_parts(forget_unknown);
+            match self.words.entry(context) {
+                Entry::Occupied(mut entry) => entry.get_mut().merge_counts(&next),
+                Entry::Vacant(entry) => {
+                    entry.insert(next);
+                }
+            }
+        }
+        self.compact();
+        removed
+    }
+
+    /// Forget everything this chain has learned, but keep its settings. The allocated memory is kept, so learning again doesn't have to reallocate it.
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.interner.clear();
+        self.casings.clear();
+        self.lengths.clear();
+        self.reverse = ReverseMap::default();
+    }
+
+    /// Remove every word combination that was counted less than `min_count` times. Contexts that have no follow-up parts left are removed as well, and so are words that are left without any word combination.
+    ///
+    /// Returns the amount of word combinations that were removed.
+    pub fn prune(&mut self, min_count: usize) -> usize {
+        let mut removed = 0;
+        self.words.retain(|_, next| {
+            removed += next.prune(min_count);
+            !next.is_empty()
+        });
+        self.compact();
+        removed
+    }
+
+    /// Remove the start of every line that didn't start with one of the `keep_top` most common starting words, so sentences can only start with common words, e.g. for `Memory::speak_any`. Ties are broken arbitrarily.
+    ///
+    /// Only the contexts right at the start of a line are removed, so the rest of the chain is left untouched, and sentences that start with one of the kept words continue like before. The lengths of the lines that started with a removed word are forgotten, see `Memory::expected_length`. Returns the amount of starting words that were removed.
+    ///
+    /// A chain with an order of 1 doesn't learn the start of a line as a context of its own, as every context is a single word, which can start a line as well as continue it. Such a chain is left unchanged, and 0 is returned.
+    pub fn restrict_starts(&mut self, keep_top: usize) -> usize {
+        if self.config.order == 1 {
+            return 0;
+        }
+        let starts = self
+            .words
+            .iter()
+            .filter(|(context, _)| context.start_word().is_some())
+            .map(|(context, next)| (context.clone(), next.total()))
+            .collect::<Vec<_>>();
+        let removed = starts.len().saturating_sub(keep_top);
+        let kept: HashSet<_> = most_counted(starts, keep_top)
+            .into_iter()
+            .map(|(context, _)| context)
+            .collect();
+        self.words
+            .retain(|context, _| context.start_word().is_none() || kept.contains(context));
+        self.compact();
+        removed
+    }
+
+    /// Get the `n` words that most often followed the given `context` words, with how often they did, sorted from the most to the least common. Ties are broken arbitrarily.
+    ///
+    /// See `Memory::transition_probability` for how the `context` words are interpreted. Returns an empty list if the context is unknown.
+    pub fn suggest(&self, context: &[&str], n: usize) -> Vec<(String, usize)> {
+        let next = match self.next_parts_for(context) {
+            Some(next) => next,
+            None => return Vec::new(),
+        };
+        let words = next
+            .iter()
+            .filter_map(|(part, count)| match *part {
+                Part::Word(word) => Some((word, count)),
+                _ => None,
+            })
+            .collect();
+        most_counted(words, n)
+            .into_iter()
+            .map(|(word, count)| (self.display(self.word(word)).to_owned(), count))
+            .collect()
+    }
+
+    /// Get the `n` words that most often followed the given `word` at the start of a line, with how often they did, like `Memory::suggest`. This is what usually comes after the first word of a sentence, which can differ a lot from what comes after the same word in the middle of one.
+    ///
+    /// Returns an empty list if no learned line started with the word.
+    ///
+    /// A chain with an order of 1 doesn't learn what followed a word at the start of a line separately, as its only context is the last word. For such a chain this returns the words that followed `word` anywhere in a line, like `Memory::suggest`, and the list isn't empty for words that only appeared in the middle of lines.
+    pub fn next_after_start(&self, word: &str, n: usize) -> Vec<(String, usize)> {
+        // Contexts with fewer words than the order start at the start of a line, except that with an order of 1 every context has exactly one word
+        self.suggest(&[word], n)
+    }
+
+    /// Get every part that followed the given `context` words, with how often it did, in no particular order. Unlike `Memory::suggest`, this includes the end of the line and isn't limited to the most common words.
+    ///
+    /// See `Memory::transition_probability` for how the `context` words are interpreted. Returns None if the context is unknown.
+    pub fn counts_for(&self, context: &[&str]) -> Option<Vec<(SentencePart, usize)>> {
+        let next = self.next_parts_for(context)?;
+        Some(
+            next.iter()
+                .map(|(part, count)| (self.resolve(*part), count))
+                .collect(),
+        )
+    }
+
+    /// Get every part that followed the given `context` words, like `Memory::counts_for`, but with the probability that it follows them instead of the count. The probabilities add up to 1.0, give or take rounding errors, which is useful for plotting or for external samplers.
+    ///
+    /// See `Memory::transition_probability` for how the `context` words are interpreted. Returns None if the context is unknown.
+    pub fn context_distribution(&self, context: &[&str]) -> Option<Vec<(SentencePart, f64)>> {
+        let next = self.next_parts_for(context)?;
+        Some(
+            next.distribution()
+                .into_iter()
+                .map(|(part, probability)| (self.resolve(*part), probability))
+                .collect(),
+        )
+    }
+
+    /// The average amount of words that followed the given `starting_word` in the learned lines that started with it. This is a good starting point for the maximum or minimum amount of words of sentences that start with the word, see `Memory::speak_bounded` and `Memory::set_min_words`. Banned words aren't counted, see `Memory::set_banlist`.
+    ///
+    /// Returns None if no learned line started with the word, or if the chain was saved by a version of this crate that didn't record the lengths of learned lines.
+    pub fn expected_length(&self, starting_word: &str) -> Option<f64> {
+        let starting_word = self.starting_word(starting_word).ok()?;
+        self.lengths.expected(self.interner.id(&starting_word)?)
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`.
+    ///
+    /// The starting word is split and normalized like the words of learned lines, so with a tokenizer that splits off punctuation "Hmm," starts with "hmm". If the starting word is not a valid word (e.g. it's multiple words), this function will always return None. Use `Memory::try_speak` to find out why no sentence was produced.
+    ///
+    /// The starting word is displayed like every other word of the sentence, so a case sensitive chain echoes it as it was given, a lowercasing chain lowercases it, and a chain with display casing uses its most common casing.
+    pub fn speak(&self, starting_word: &str) -> Option<String> {
+        self.try_speak(starting_word).ok()
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but returns the reason why no sentence could be produced.
+    pub fn try_speak(&self, starting_word: &str) -> Result<String, SpeakError> {
+        self.speak_inner(
+            starting_word,
+            &mut rand::thread_rng(),
+            &SpeakOptions::default(),
+        )
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but tries again up to `max_attempts` times in total when the sentence ended right after the starting word. This way a starting word that is usually followed by more words doesn't occasionally produce nothing.
+    ///
+    /// Still returns None if every attempt failed, e.g. because the starting word was only ever followed by the end of a line. Returns None if `max_attempts` is 0.
+    pub fn speak_with_attempts(&self, starting_word: &str, max_attempts: usize) -> Option<String> {
+        let mut rng = rand::thread_rng();
+        for _ in 0..max_attempts {
+            match self.speak_inner(starting_word, &mut rng, &SpeakOptions::default()) {
+                Ok(sentence) => return Some(sentence),
+                Err(SpeakError::NoTransitions) => {}
+                // Retrying doesn't make the starting word valid
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but never ends the sentence right after the starting word. The first word after the starting word is only picked out of the words that followed it, ignoring how often the starting word ended a line.
+    ///
+    /// Still returns None if the starting word was only ever followed by the end of a line.
+    pub fn speak_nonempty(&self, starting_word: &str) -> Option<String> {
+        let options = SpeakOptions {
+            nonempty: true,
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but uses the given `rng` to pick the words.
+    ///
+    /// Passing in a seeded rng (e.g. `rand::rngs::StdRng::seed_from_u64`) will produce the same sentence on every run. When speaking from many threads at once, every thread can keep its own rng and pass it in here.
+    pub fn speak_with_rng<R: Rng>(&self, starting_word: &str, rng: &mut R) -> Option<String> {
+        self.speak_with_options(starting_word, &SpeakOptions::default(), rng)
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak_with_rng`, but generated as configured by `options`. This combines the modes of the other `speak_*` methods, e.g. a temperature with a maximum amount of words, and makes all of them reproducible with a seeded rng.
+    pub fn speak_with_options<R: Rng>(
+        &self,
+        starting_word: &str,
+        options: &SpeakOptions,
+        rng: &mut R,
+    ) -> Option<String> {
+        self.speak_inner(starting_word, rng, options).ok()
+    }
+
+    /// Start a conversation with this chain, which picks words with an rng seeded with `seed`, see `Session`. This is useful for bots that should be reproducible within a session, but vary between sessions.
+    ///
+    /// The sentences of a seed may change with new versions of this crate or of `rand`.
+    pub fn session(&self, seed: u64) -> Session<'_> {
+        Session::new(self, seed)
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but stops after at most `max_words` words have been added to the `starting_word`.
+    ///
+    /// Returns None if `max_words` is 0.
+    pub fn speak_bounded(&self, starting_word: &str, max_words: usize) -> Option<String> {
+        let options = SpeakOptions {
+            max_words: Some(max_words),
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but only ends the sentence where a learned line ended, instead of randomly breaking it off.
+    ///
+    /// As there may be no end in sight, the sentence stops after at most `max_words` words have been added to the `starting_word`.
+    pub fn speak_natural(&self, starting_word: &str, max_words: usize) -> Option<String> {
+        let options = SpeakOptions {
+            max_words: Some(max_words),
+            natural_ending: true,
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but picks every next word with the given `temperature`.
+    ///
+    /// A temperature of 1.0 behaves like `Memory::speak`. Higher temperatures make rare words more likely, and lower temperatures make common words more likely. A temperature of 0.0 always picks the most common next word. See `NextPartList::get_with_temperature`.
+    pub fn speak_with_temperature(&self, starting_word: &str, temperature: f64) -> Option<String> {
+        let options = SpeakOptions {
+            sampler: Sampler::Temperature(temperature),
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but only picks every next word from the `k` most common next words.
+    ///
+    /// With a `k` of 1 this always picks the most common next word. See `NextPartList::get_top_k`.
+    pub fn speak_top_k(&self, starting_word: &str, k: usize) -> Option<String> {
+        let options = SpeakOptions {
+            sampler: Sampler::TopK(k),
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, but never picks a next word that followed its context less often than `min_prob` of the time. The sentence ends early if no next word is that likely.
+    ///
+    /// This prunes the rare transitions of a context, which keeps the output close to the text that was learned. See `NextPartList::get_min_probability`.
+    pub fn speak_min_prob(&self, starting_word: &str, min_prob: f64) -> Option<String> {
+        let options = SpeakOptions {
+            sampler: Sampler::MinProbability(min_prob),
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence that starts with the given word, like `Memory::speak`, but backs off when the chain runs into an unknown combination of words.
+    ///
+    /// If the last words are unknown, this continues from any context that ends in the last word. If even that word is unknown, this continues from a random start of a line instead. This is slower than `Memory::speak` when it has to back off, because it walks the entire chain once.
+    pub fn speak_smoothed(&self, starting_word: &str) -> Option<String> {
+        let options = SpeakOptions {
+            smoothing: true,
+            ..SpeakOptions::default()
+        };
+        self.speak_with_options(starting_word, &options, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, that tries to reach the `target` word. Every next word is still picked randomly, but words that lead to the `target` in fewer steps are preferred.
+    ///
+    /// Until the `target` is reached, the sentence isn't randomly broken off, and only words that can still reach the `target` are picked, if there are any. Afterwards the sentence continues like `Memory::speak`. The sentence stops after at most `max_words` words have been added to the `starting_word`, so it may not contain the `target`.
+    ///
+    /// This walks the entire chain to find the distances to the `target`. Returns None if the `target` was never learned.
+    pub fn speak_toward(
+        &self,
+        starting_word: &str,
+        target: &str,
+        max_words: usize,
+    ) -> Option<String> {
+        let starting_word = self.starting_word(starting_word).ok()?;
+        let target = self.id(target)?;
+        let starting_id = self.interner.id(&starting_word)?;
+        let previous_context = SentenceContext::with_previous_word(self.config.order, starting_id);
+        let options = SpeakOptions {
+            max_words: Some(max_words),
+            ..SpeakOptions::default()
+        };
+        let mut generator = Generator::new(self, previous_context, rand::thread_rng(), options);
+        if starting_id != target {
+            generator = generator.steered(Steering::new(self, target));
+        }
+        let words: Vec<_> = generator.collect();
+        self.join(&starting_word, &words)
+    }
+
+    /// Tries to produce a sentence that continues from the two given words, e.g. from the middle of a sentence.
+    ///
+    /// Returns None if `first` was never followed by `second`. For a chain with an order of 1 only `second` is used, and for a chain with an order above 2 the phrase is treated as the start of a line.
+    pub fn speak_from_phrase(&self, first: &str, second: &str) -> Option<String> {
+        let context = self.context_from_words(&[first, second])?;
+        let first = self.normalize(first);
+        let second = self.normalize(second);
+        let mut words = vec![second.as_ref()];
+        words.extend(self.continue_sentence(
+            context,
+            &mut rand::thread_rng(),
+            &SpeakOptions::default(),
+        ));
+        if words.len() == 1 {
+            // The context is unknown, or was only ever followed by __END__
+            return None;
+        }
+        self.join(&first, &words)
+    }
+
+    fn speak_inner<R: Rng>(
+        &self,
+        starting_word: &str,
+        rng: &mut R,
+        options: &SpeakOptions,
+    ) -> Result<String, SpeakError> {
+        let starting_word = self.starting_word(starting_word)?;
+
+        // We always start with __START__, ..., starting_word
+        let previous_context = match self.interner.id(&starting_word) {
+            Some(word) => SentenceContext::with_previous_word(self.config.order, word),
+            // A context of only __START__ is never learned, but smoothing can still back off from it
+            None if options.smoothing => SentenceContext::new(self.config.order),
+            None => return Err(SpeakError::NoTransitions),
+        };
+        let words = self.continue_sentence(previous_context, rng, options);
+        self.join(&starting_word, &words)
+            .ok_or(SpeakError::NoTransitions)
+    }
+
+    /// Check that `starting_word` is a single word, and split and normalize it like a learned word. If the tokenizer splits off punctuation, the first word that isn't punctuation is used, so "Hmm," starts with "hmm".
+    fn starting_word<'a>(&self, starting_word: &'a str) -> Result<Cow<'a, str>, SpeakError> {
+        if starting_word.is_empty() {
+            return Err(SpeakError::EmptyInput);
+        }
+        if starting_word.chars().any(char::is_whitespace) {
+            return Err(SpeakError::ContainsWhitespace);
+        }
+        let tokens = self.tokens(starting_word);
+        let tokenizer = self.config.tokenizer;
+        let token = tokens
+            .iter()
+            .find(|token| !tokenizer.is_punctuation(token))
+            .or_else(|| tokens.first())
+            // The word is too long to have been learned
+            .ok_or(SpeakError::NoTransitions)?;
+        Ok(self.normalize(token))
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, and also returns every decision that was made along the way. This helps to explain why the chain produced a given sentence.
+    ///
+    /// The last decision is usually the `SentencePart::EndOfLine` that ended the sentence, unless the sentence was broken off.
+    pub fn speak_traced(&self, starting_word: &str) -> Option<(String, Vec<Decision>)> {
+        let starting_word = self.starting_word(starting_word).ok()?;
+        let previous_context = SentenceContext::with_previous_word(
+            self.config.order,
+            self.interner.id(&starting_word)?,
+        );
+        let mut generator = Generator::new(
+            self,
+            previous_context,
+            rand::thread_rng(),
+            SpeakOptions::default(),
+        )
+        .traced();
+        let words: Vec<_> = generator.by_ref().collect();
+        let sentence = self.join(&starting_word, &words)?;
+        Some((sentence, generator.into_trace()))
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, and also returns why the sentence ended. This helps to tune the settings of the chain, e.g. a chain that often runs into dead ends needs more lines to learn from.
+    pub fn speak_detailed(&self, starting_word: &str) -> Option<(String, StopReason)> {
+        let starting_word = self.starting_word(starting_word).ok()?;
+        let previous_context = SentenceContext::with_previous_word(
+            self.config.order,
+            self.interner.id(&starting_word)?,
+        );
+        let mut generator = Generator::new(
+            self,
+            previous_context,
+            rand::thread_rng(),
+            SpeakOptions::default(),
+        );
+        let words: Vec<_> = generator.by_ref().collect();
+        let sentence = self.join(&starting_word, &words)?;
+        Some((sentence, generator.stop_reason()?))
+    }
+
+    /// Start generating a sentence in chunks, starting with the given `starting_word`, see `Memory::continue_speak`. Returns None if the starting word is not a single word, or was never learned.
+    pub fn start_speaking(&self, starting_word: &str) -> Option<GenerationState> {
+        let starting_word = self.starting_word(starting_word).ok()?;
+        let context = SentenceContext::with_previous_word(
+            self.config.order,
+            self.interner.id(&starting_word)?,
+        );
+        Some(GenerationState::new(context))
+    }
+
+    /// Continue the sentence of `state` with the next chunk of words, and update `state` so the next call continues where this one left off. Every chunk ends where `Memory::speak` would end a sentence, but chunks that were randomly broken off can be continued. This is useful for "keep going" buttons.
+    ///
+    /// Returns only the words of the new chunk, joined like a sentence. Returns None if no words could be added, e.g. because the sentence reached the end of a line, see `GenerationState::is_finished`.
+    pub fn continue_speak<R: Rng>(
+        &self,
+        state: &mut GenerationState,
+        rng: &mut R,
+    ) -> Option<String> {
+        if state.is_finished() {
+            return None;
+        }
+        let mut generator = Generator::resume(self, state, rng, SpeakOptions::default());
+        let words: Vec<_> = generator.by_ref().collect();
+        generator.save_state(state);
+        let (first, rest) = words.split_first()?;
+        // The chunk continues the sentence, so it isn't capitalized. `join_words` only returns None for a single word
+        Some(
+            self.join_words(first, rest)
+                .unwrap_or_else(|| self.display(first).to_owned()),
+        )
+    }
+
+    /// Lazily generates the words that follow the given `starting_word`, one word at a time. The `starting_word` itself is not included.
+    ///
+    /// This picks words like `Memory::speak` does, so the iterator ends at the end of a line, when the chain runs out of known words, or when the sentence is randomly broken off.
+    pub fn words(&self, starting_word: &str) -> impl Iterator<Item = String> + '_ {
+        // A context of only __START__ is never learned, so unknown words produce no words
+        let starting_id = self
+            .starting_word(starting_word)
+            .ok()
+            .and_then(|starting_word| self.interner.id(&starting_word));
+        let previous_context = match starting_id {
+            Some(word) => SentenceContext::with_previous_word(self.config.order, word),
+            None => SentenceContext::new(self.config.order),
+        };
+        Generator::new(
+            self,
+            previous_context,
+            rand::thread_rng(),
+            SpeakOptions::default(),
+        )
+        .map(str::to_owned)
+    }
+
+    /// Tries to produce up to `n` distinct sentences starting with the given `starting_word`, see `Memory::speak`.
+    ///
+    /// If the chain can't produce `n` distinct sentences, fewer are returned. Returns an empty list if the `starting_word` has no known follow-up words.
+    pub fn speak_n(&self, starting_word: &str, n: usize) -> Vec<String> {
+        // Give up after a while, the chain might not know enough sentences
+        const ATTEMPTS_PER_SENTENCE: usize = 10;
+
+        let is_known = self
+            .starting_word(starting_word)
+            .is_ok_and(|starting_word| self.next_parts_for(&[&starting_word]).is_some());
+        if !is_known {
+            return Vec::new();
+        }
+
+        let mut rng = rand::thread_rng();
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        for _ in 0..n.saturating_mul(ATTEMPTS_PER_SENTENCE) {
+            if result.len() >= n {
+                break;
+            }
+            if let Some(sentence) = self.speak_with_rng(starting_word, &mut rng) {
+                if seen.insert(sentence.clone()) {
+                    result.push(sentence);
+                }
+            }
+        }
+        result
+    }
+
+    /// Tries to produce a sentence starting with a random word that started one of the learned lines.
+    ///
+    /// Words that started more lines are more likely to be picked. For a chain with an order of 1, any learned word can be picked.
+    pub fn speak_any(&self) -> Option<String> {
+        self.speak_any_where(|_| true)
+    }
+
+    /// Tries to produce a sentence starting with a random word that started one of the learned lines, like `Memory::speak_any`, but skips the starts that usually ended the line right away. This produces fewer one word sentences.
+    ///
+    /// A start is skipped if the chance that the line ended after it is above `max_end_probability`, e.g. 0.9 skips starts that ended more than 90% of their lines. Returns None if every start is skipped.
+    pub fn speak_any_lasting(&self, max_end_probability: f64) -> Option<String> {
+        self.speak_any_where(|next| next.probability(&Part::EndOfLine) <= max_end_probability)
+    }
+
+    /// Pick a random word that started one of the learned lines, like `Memory::speak_any` does, without generating a sentence. This is useful to suggest prompts, which can be passed to `Memory::speak` afterwards.
+    ///
+    /// Words that started more lines are more likely to be picked. For a chain with an order of 1, any learned word can be picked. Returns None if the chain learned nothing.
+    pub fn random_start<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        let word = self.pick_start_context(rng)?.start_word()?;
+        Some(self.display(self.word(word)).to_owned())
+    }
+
+    /// Tries to produce a sentence starting with a random start context for which `filter` returns true
+    fn speak_any_where(&self, filter: impl Fn(&NextPartList) -> bool) -> Option<String> {
+        let mut rng = rand::thread_rng();
+        let context = self.pick_start_context_where(&mut rng, filter)?;
+
+        let starting_word = self.word(context.start_word()?);
+        let words = self.continue_sentence(context.clone(), &mut rng, &SpeakOptions::default());
+        self.join(starting_word, &words)
+    }
+
+    /// Pick a random context that directly follows __START__, weighed by how often it was seen
+    pub(crate) fn pick_start_context(&self, rng: &mut impl Rng) -> Option<&SentenceContext> {
+        self.pick_start_context_where(rng, |_| true)
+    }
+
+    /// Pick a random context that directly follows __START__, like `Memory::pick_start_context`, but only out of the contexts for which `filter` returns true
+    fn pick_start_context_where(
+        &self,
+        rng: &mut impl Rng,
+        filter: impl Fn(&NextPartList) -> bool,
+    ) -> Option<&SentenceContext> {
+        let starts = self
+            .words
+            .iter()
+            .filter(|(context, next)| {
+                // Lines that started with a word before it was banned don't start generated sentences
+                context
+                    .start_word()
+                    .is_some_and(|word| !self.is_banned(word))
+                    && filter(next)
+            })
+            .map(|(context, next)| (context, next.total()));
+        pick_by_count(rng, starts)
+    }
+
+    /// Group every context by its last part, together with how often it was seen. This is used to back off from unknown contexts, see `Memory::speak_smoothed`.
+    pub(crate) fn contexts_by_last_part(&self) -> HashMap<Part, Vec<(&SentenceContext, usize)>> {
+        let mut result = HashMap::<_, Vec<_>>::new();
+        for (context, next) in &self.words {
+            if let Some(&last) = context.parts().last() {
+                result
+                    .entry(last)
+                    .or_default()
+                    .push((context, next.total()));
+            }
+        }
+        result
+    }
+
+    /// Create a read-only copy of this chain, which can generate sentences without the standard library, see `FrozenChain`.
+    ///
+    /// Banned words are left out of the copy, so it never generates them, see `Memory::set_banlist`. Not every setting of this chain applies to the copy, see `FrozenChain` for the ones that are ignored.
+    pub fn freeze(&self) -> FrozenChain {
+        let words = self
+            .interner
+            .words()
+            .map(|word| self.display(word).to_owned())
+            .collect();
+        let mut ids: Vec<_> = self
+            .interner
+            .words()
+            .enumerate()
+            .map(|(id, word)| (word.to_owned(), id as WordId))
+            .filter(|&(_, id)| !self.is_banned(id))
+            .collect();
+        ids.sort_unstable();
+        // The frozen chain doesn't know the banlist, so banned words are left out of it entirely
+        let mut contexts: Vec<_> = self
+            .words
+            .iter()
+            .filter_map(|(context, next)| {
+                let next: Vec<_> = next
+                    .iter()
+                    .filter(|(part, _)| !matches!(**part, Part::Word(word) if self.is_banned(word)))
+                    .map(|(part, count)| (*part, count))
+                    .collect();
+                if next.is_empty() {
+                    return None;
+                }
+                Some((context.parts().to_vec(), next))
+            })
+            .collect();
+        contexts.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        FrozenChain {
+            order: self.config.order,
+            normalization: self.config.normalization,
+            tokenizer: self.config.tokenizer,
+            break_chance_per_three_words: self.config.break_chance_per_three_words,
+            max_repeat: self.config.max_repeat,
+            min_words: self.config.min_words,
+            max_steps: self.config.max_steps,
+            join_with: self.config.join_with.clone(),
+            capitalize_first: self.config.capitalize_first,
+            words,
+            ids,
+            contexts,
+        }
+    }
+
+    /// Build an index of the contexts that lead to every context, which can be used to generate sentences backwards. See `ReverseIndex::speak_backward`.
+    ///
+    /// This walks the entire chain, so when generating multiple sentences it's best to build the index once and reuse it. Bidirectional chains keep the index up to date themselves, so for them this is cheap, see `Memory::set_bidirectional`.
+    pub fn reverse_index(&self) -> ReverseIndex<'_> {
+        ReverseIndex::new(self)
+    }
+
+    /// Tries to produce a sentence that ends with the given `ending_word`, by generating words backwards until the start of a line.
+    ///
+    /// Unless the chain is bidirectional, this builds a new `ReverseIndex` on every call, see `Memory::reverse_index`.
+    pub fn speak_backward(&self, ending_word: &str) -> Option<String> {
+        self.reverse_index().speak_backward(ending_word)
+    }
+
+    /// Normalizes the given word with the normalization of this chain. See `Normalization::apply`.
+    pub(crate) fn normalize<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        self.config.normalization.apply(word)
+    }
+
+    /// Get the id of the given word, after normalizing it. Returns None if the chain never learned the word.
+    pub(crate) fn id(&self, word: &str) -> Option<WordId> {
+        self.interner.id(&self.normalize(word))
+    }
+
+    /// Get the word with the given id
+    pub(crate) fn word(&self, id: WordId) -> &str {
+        self.interner.word(id)
+    }
+
+    /// Turn a part of this chain into the equivalent public `SentencePart`
+    pub(crate) fn resolve(&self, part: Part) -> SentencePart {
+        match part {
+            Part::StartOfLine => SentencePart::StartOfLine,
+            Part::EndOfLine => SentencePart::EndOfLine,
+            Part::Word(id) => SentencePart::Word(self.word(id).to_owned()),
+            Part::Unknown => SentencePart::Unknown,
+        }
+    }
+
+    /// Turn a `SentencePart` into the equivalent part of this chain like `Memory::intern_part`, but normalize its word first
+    pub(crate) fn intern_normalized(&mut self, part: &SentencePart) -> Part {
+        match part {
+            SentencePart::Word(word) => {
+                let word = self.normalize(word).into_owned();
+                Part::Word(self.interner.intern(&word))
+            }
+            part => self.intern_part(part),
+        }
+    }
+
+    /// Turn a `SentencePart` into the equivalent part of this chain, interning its word if necessary
+    pub(crate) fn intern_part(&mut self, part: &SentencePart) -> Part {
+        match part {
+            SentencePart::StartOfLine => Part::StartOfLine,
+            SentencePart::EndOfLine => Part::EndOfLine,
+            SentencePart::Word(word) => Part::Word(self.interner.intern(word)),
+            SentencePart::Unknown => Part::Unknown,
+        }
+    }
+
+    /// Build a context from the given words, normalizing every word. Returns None if any of the words in the context was never learned, as the context can't be known then.
+    fn context_from_words(&self, words: &[&str]) -> Option<SentenceContext> {
+        // Only the last `order` words end up in the context
+        let words = words[words.len().saturating_sub(self.config.order)..]
+            .iter()
+            .map(|word| self.id(word))
+            .collect::<Option<Vec<_>>>()?;
+        Some(SentenceContext::from_words(self.config.order, words))
+    }
+
+    /// Get the parts that followed the given `context` words, see `Memory::transition_probability` for how they are interpreted. Returns None if the context is unknown.
+    ///
+    /// Unlike `Memory::context_from_words`, this doesn't allocate a context for chains with an order of up to `INLINE_ORDER`.
+    fn next_parts_for(&self, words: &[&str]) -> Option<&NextPartList> {
+        let order = self.config.order;
+        let mut inline = [Part::StartOfLine; INLINE_ORDER];
+        let mut heap;
+        let parts = if order <= INLINE_ORDER {
+            &mut inline[..order]
+        } else {
+            heap = vec![Part::StartOfLine; order];
+            &mut heap[..]
+        };
+        // Only the last `order` words end up in the context, after the __START__ padding
+        let words = &words[words.len().saturating_sub(order)..];
+        let padding = order - words.len();
+        for (part, word) in parts[padding..].iter_mut().zip(words) {
+            *part = Part::Word(self.id(word)?);
+        }
+        self.words.get(&parts[..])
+    }
+
+    /// Join the `starting_word` and the generated `words` into a sentence, and capitalize it if configured. Returns None if no words were generated.
+    pub(crate) fn join(&self, starting_word: &str, words: &[&str]) -> Option<String> {
+        let mut result = self.join_words(starting_word, words)?;
+        if self.config.capitalize_first {
+            tokenizer::capitalize_first(&mut result);
+        }
+        Some(result)
+    }
+
+    /// Join the `first` word and the following `words`, like `Memory::join`, but without capitalizing them, e.g. for the middle of a sentence
+    fn join_words(&self, first: &str, words: &[&str]) -> Option<String> {
+        if words.is_empty() {
+            return None;
+        }
+        let mut result = self.display(first).to_owned();
+        for word in words {
+            if !self.config.tokenizer.is_punctuation(word) {
+                result += &self.config.join_with;
+            }
+            result += self.display(word);
+        }
+        Some(result)
+    }
+
+    /// Get the casing the given normalized word should be displayed with
+    pub(crate) fn display<'a>(&'a self, word: &'a str) -> &'a str {
+        if self.config.display_casing {
+            self.casings.display(word)
+        } else {
+            word
+        }
+    }
+
+    /// Keeps picking random follow-up words for the given context, as configured by `options`. Returns the picked words.
+    fn continue_sentence<R: Rng>(
+        &self,
+        previous_context: SentenceContext,
+        rng: &mut R,
+        options: &SpeakOptions,
+    ) -> Vec<&str> {
+        Generator::new(self, previous_context, rng, options.clone()).collect()
+    }
+
+    /// Randomly decides if a sentence should end after `len` words were added.
+    pub(crate) fn should_break(&self, len: usize, rng: &mut impl Rng) -> bool {
+        if len < self.config.min_words {
+            return false;
+        }
+        // We don't want to get in an infinite loop,
+        // so we add a chance to break at the current word, for each 3 words we added
+        let chance_to_break = (len / 3)
+            .saturating_mul(self.config.break_chance_per_three_words as usize)
+            .min(100);
+        rng.gen_bool(chance_to_break as f64 / 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bytes of the given chain, to compare chains
+    fn saved(memory: &Memory) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        memory.save_raw(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn merging_a_different_order_fails_without_changes() {
+        let mut memory = Memory::with_order(2);
+        memory.learn("the cat sat on the mat");
+        let before = saved(&memory);
+
+        let mut other = Memory::with_order(3);
+        other.learn("the dog sat on the log");
+        match memory.merge_checked(other) {
+            Err(Error::OrderMismatch { expected, found }) => assert_eq!((expected, found), (2, 3)),
+            result => panic!("Expected an order mismatch, got {:?}", result),
+        }
+        assert_eq!(saved(&memory), before);
+        assert_eq!(memory.order(), 2);
+    }
+
+    #[test]
+    fn merging_the_same_order_succeeds() {
+        let mut memory = Memory::with_order(3);
+        memory.learn("the cat sat on the mat");
+        let mut other = Memory::with_order(3);
+        other.learn("the dog sat on the log");
+        memory.merge_checked(other).unwrap();
+        assert!(
+            memory
+                .transition_probability(&["the", "dog"], "sat")
+                .unwrap()
+                > 0.0
+        );
+    }
+
+    #[test]
+    fn loading_an_order_of_0_fails() {
+        let mut memory = Memory::default();
+        memory.learn("the cat sat on the mat");
+        memory.config.order = 0;
+
+        match Memory::load_raw(&saved(&memory)[..]) {
+            Err(Error::InvalidOrder(0)) => {}
+            result => panic!("Expected an invalid order, got {:?}", result),
+        }
+        let mut zip = std::io::Cursor::new(Vec::new());
+        memory.save_to(&mut zip).unwrap();
+        zip.set_position(0);
+        match Memory::load_from(zip) {
+            Err(Error::InvalidOrder(0)) => {}
+            result => panic!("Expected an invalid order, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn importing_an_order_of_0_fails() {
+        let mut memory = Memory::default();
+        memory.learn("the cat sat on the mat");
+        let mut json = Vec::new();
+        memory.export_json(&mut json).unwrap();
+        let json = String::from_utf8(json)
+            .unwrap()
+            .replace(r#""order":2"#, r#""order":0"#);
+
+        match Memory::import_json(json.as_bytes()) {
+            Err(Error::InvalidOrder(0)) => {}
+            result => panic!("Expected an invalid order, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn validating_an_order_of_0_fails() {
+        let mut memory = Memory::default();
+        memory.learn("the cat sat on the mat");
+        assert_eq!(memory.validate(), Ok(()));
+        memory.config.order = 0;
+        let errors = memory.validate().unwrap_err();
+        assert_eq!(errors[0], ValidationError::InvalidOrder(0));
+    }
+
+    #[test]
+    fn restricting_the_starts_of_an_order_of_1_keeps_the_chain() {
+        let mut memory = Memory::with_order(1);
+        memory.learn("the cat sat on the mat");
+        memory.learn("a dog sat on a log");
+        let before = saved(&memory);
+        assert_eq!(memory.restrict_starts(1), 0);
+        assert_eq!(saved(&memory), before);
+        assert_eq!(memory.suggest(&["sat"], 1), vec![("on".to_owned(), 2)]);
+    }
+
+    #[test]
+    fn banned_words_dont_count_towards_lengths() {
+        let mut memory = Memory::with_banlist(vec!["bad".to_owned()]);
+        memory.learn("hi bad there");
+        assert_eq!(memory.counts_for(&["hi"]), None);
+        assert_eq!(memory.expected_length("hi"), None);
+
+        memory.learn("hello there bad friend");
+        assert!(memory.counts_for(&["hello"]).is_some());
+        assert_eq!(memory.expected_length("hello"), Some(2.0));
+        memory.unlearn("hello there bad friend");
+        assert_eq!(memory.expected_length("hello"), None);
+    }
+
+    #[test]
+    fn unlearning_keeps_the_reverse_map_up_to_date() {
+        let mut memory = Memory::default();
+        memory.set_bidirectional(true);
+        memory.learn("a x y");
+        memory.learn("b x y");
+        memory.learn("c z");
+        memory.unlearn("b x y");
+        memory.unlearn("c z");
+        memory.unlearn("d e f");
+        let index = memory.reverse_index();
+        for _ in 0..100 {
+            assert_eq!(index.speak_backward("y").as_deref(), Some("a x y"));
+        }
+        assert_eq!(index.speak_backward("z"), None);
+    }
+
+    #[test]
+    fn reading_skips_lines_that_arent_utf8() {
+        let mut memory = Memory::default();
+        let logs: &[u8] = b"<a> hello there\n<b> bad \xff bytes\nno nick\n<c> hello world\n";
+        let learned = memory.learn_reader_with(logs, |line| line.split('>').nth(1));
+        assert_eq!(learned.unwrap(), 2);
+        let mut next = memory.suggest(&["hello"], 2);
+        next.sort_unstable();
+        assert_eq!(next, vec![("there".to_owned(), 1), ("world".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn banned_words_are_never_generated() {
+        let mut memory = Memory::default();
+        memory.learn("bad dogs bark at night");
+        memory.learn("good dogs bark at bad cats");
+        memory.learn("the night is bad");
+        memory.learn("the cats sleep at night");
+        memory.set_banlist(vec!["bad".to_owned()]);
+        let is_clean = |sentence: &str| sentence.split(' ').all(|word| word != "bad");
+
+        let mut rng = rand::thread_rng();
+        let index = memory.reverse_index();
+        for _ in 0..200 {
+            for sentence in [
+                memory.speak("dogs"),
+                memory.speak("at"),
+                memory.speak_any(),
+                memory.random_start(&mut rng),
+                index.speak_backward("night"),
+                index.speak_backward("cats"),
+            ]
+            .iter()
+            .flatten()
+            {
+                assert!(is_clean(sentence), "{:?} contains a banned word", sentence);
+            }
+            assert_eq!(index.speak_backward("bad"), None);
+        }
+
+        memory.set_continue_across_lines(true);
+        memory.set_min_words(20);
+        for _ in 0..200 {
+            if let Some(sentence) = memory.speak("the") {
+                assert!(is_clean(&sentence), "{:?} contains a banned word", sentence);
+            }
+        }
+    }
+
+    #[test]
+    fn threads_speak_from_a_shared_chain() {
+        let mut memory = Memory::default();
+        memory.learn("the cat sat on the mat");
+        memory.learn("the dog sat on the log");
+        memory.learn("the bird flew over the house");
+        let memory = std::sync::Arc::new(memory);
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let memory = std::sync::Arc::clone(&memory);
+                std::thread::spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    (0..100)
+                        .map(|_| memory.speak_with_rng("the", &mut rng))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for thread in threads {
+            for sentence in thread.join().unwrap() {
+                assert!(sentence.unwrap().starts_with("the "));
+            }
         }
     }
 }
@@ -0,0 +1,33 @@
+use crate::{Memory, Normalization};
+use serde::{Deserialize, Serialize};
+
+/// A summary of a saved chain, which is saved next to it, so it can be read with `Memory::load_meta` without loading the chain itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Meta {
+    /// The version of the saved format the chain was saved in
+    pub version: u32,
+    /// The order of the chain, see `Memory::order`
+    pub order: usize,
+    /// The way the chain normalizes words, see `Memory::with_normalization`
+    pub normalization: Normalization,
+    /// The amount of contexts the chain has learned, see `Memory::state_count`
+    pub states: usize,
+    /// The amount of distinct word combinations the chain has learned, see `Memory::transition_count`
+    pub transitions: usize,
+    /// The amount of distinct words the chain has learned, see `Memory::vocabulary`
+    pub vocabulary: usize,
+}
+
+impl Meta {
+    /// Summarize the given chain, as it is saved in the given format `version`
+    pub(crate) fn new(memory: &Memory, version: u32) -> Self {
+        Self {
+            version,
+            order: memory.order(),
+            normalization: memory.config.normalization,
+            states: memory.state_count(),
+            transitions: memory.transition_count(),
+            vocabulary: memory.vocabulary(),
+        }
+    }
+}
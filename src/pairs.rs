@@ -0,0 +1,54 @@
+//! Serializes a `HashMap` as a list of `(key, value)` pairs, so maps with keys that aren't strings can still be written to formats like JSON.
+//!
+//! Use with `#[serde(with = "crate::pairs")]`. For bincode this produces the same bytes as serializing the map itself. The pairs are sorted by key, so equal maps always serialize to the same bytes, regardless of the order of the `HashMap`.
+
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+pub fn serialize<K, V, H, S>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Serialize + Ord,
+    V: Serialize,
+    S: Serializer,
+{
+    let mut pairs: Vec<_> = map.iter().collect();
+    pairs.sort_unstable_by_key(|(key, _)| *key);
+    serializer.collect_seq(pairs)
+}
+
+pub fn deserialize<'de, K, V, H, D>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_seq(PairsVisitor(PhantomData))
+}
+
+struct PairsVisitor<K, V, H>(PhantomData<(K, V, H)>);
+
+impl<'de, K, V, H> Visitor<'de> for PairsVisitor<K, V, H>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    type Value = HashMap<K, V, H>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of (key, value) pairs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut map = HashMap::with_capacity_and_hasher(seq.size_hint().unwrap_or(0), H::default());
+        while let Some((key, value)) = seq.next_element()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
@@ -0,0 +1,72 @@
+//! The parts of a sentence as they are stored in a chain, and the pieces of generation that are shared with `FrozenChain`. This doesn't need the standard library.
+
+use serde::{Deserialize, Serialize};
+
+/// The id of a word in the `Interner` of a chain
+pub type WordId = u32;
+
+/// A sentence part as it is stored in a chain, with every word replaced by its id in the `Interner` of the chain. See `SentencePart` for the parts that are exposed
+#[derive(Hash, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize, Clone, Copy)]
+pub enum Part {
+    /// The start of a line, before the first word
+    StartOfLine,
+    /// The end of a line, after the last word
+    EndOfLine,
+    /// The id of a single word
+    Word(WordId),
+    /// A word that was removed from the vocabulary
+    Unknown,
+}
+
+impl Part {
+    /// Check if a given Part is a Part::Word
+    #[cfg(feature = "std")]
+    pub fn is_word(&self) -> bool {
+        matches!(self, Part::Word(_))
+    }
+
+    /// Get the id of the word of a Part::Word
+    #[cfg(feature = "std")]
+    pub fn word_id(&self) -> Option<WordId> {
+        match *self {
+            Part::Word(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// Get a random item, weighed by the given counts. Returns None if all counts are 0.
+pub fn pick_by_count<T>(
+    rng: &mut impl rand::Rng,
+    counts: impl Iterator<Item = (T, usize)> + Clone,
+) -> Option<T> {
+    let total: usize = counts.clone().map(|(_, count)| count).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut index = rng.gen_range(0, total);
+    counts
+        .filter(|&(_, count)| {
+            let is_right_item = count > index;
+            index = index.wrapping_sub(count);
+            is_right_item
+        })
+        .map(|(item, _)| item)
+        .next()
+}
+
+/// The longest sequence of words that `repeats_too_often` checks
+const MAX_REPEATED_SEQUENCE: usize = 4;
+
+/// Checks if any sequence of words at the end of `history` appears more than `max_repeat` times back to back
+pub fn repeats_too_often(history: &[WordId], max_repeat: usize) -> bool {
+    (1..=MAX_REPEATED_SEQUENCE).any(|len| {
+        let mut chunks = history.rchunks_exact(len);
+        let last = match chunks.next() {
+            Some(last) => last,
+            None => return false,
+        };
+        let repeats = 1 + chunks.take_while(|chunk| chunk == &last).count();
+        repeats > max_repeat
+    })
+}
@@ -0,0 +1,46 @@
+use std::io::{self, Write};
+
+/// The amount of bytes that is written between two progress reports
+const REPORT_INTERVAL: u64 = 64 * 1024;
+
+/// Wraps a writer, and reports the total amount of bytes written to it every `REPORT_INTERVAL` bytes
+pub struct ProgressWriter<W, F> {
+    writer: W,
+    progress: F,
+    written: u64,
+    reported: u64,
+}
+
+impl<W: Write, F: FnMut(u64)> ProgressWriter<W, F> {
+    pub fn new(writer: W, progress: F) -> Self {
+        Self {
+            writer,
+            progress,
+            written: 0,
+            reported: 0,
+        }
+    }
+
+    /// Report the final amount of bytes, if it wasn't reported yet
+    pub fn finish(mut self) {
+        if self.reported != self.written || self.written == 0 {
+            (self.progress)(self.written);
+        }
+    }
+}
+
+impl<W: Write, F: FnMut(u64)> Write for ProgressWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.written += written as u64;
+        if self.written - self.reported >= REPORT_INTERVAL {
+            self.reported = self.written;
+            (self.progress)(self.written);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
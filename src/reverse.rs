@@ -0,0 +1,224 @@
+use crate::part::pick_by_count;
+use crate::words::map_heap_size;
+use crate::{Map, Memory, NextPartList, Part, SentenceContext, WordId};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The contexts that lead to every context of a chain, see `ReverseIndex`. Chains that learn bidirectionally keep this up to date while learning, see `Memory::set_bidirectional`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct ReverseMap {
+    /// For every context, the oldest parts of the contexts that lead to it. E.g. for an order of 2, learning "a b c" counts `a` towards the context (b, c)
+    #[serde(with = "crate::pairs")]
+    previous: Map<SentenceContext, NextPartList>,
+    /// For every word, the contexts ending in that word that were followed by __END__
+    #[serde(with = "crate::pairs")]
+    ends: Map<WordId, Vec<SentenceContext>>,
+}
+
+impl ReverseMap {
+    /// Build the reverse map of the given contexts of a chain
+    pub(crate) fn build(words: &Map<SentenceContext, NextPartList>) -> Self {
+        let mut map = Self::default();
+        for (context, next) in words {
+            for (&part, count) in next.iter() {
+                map.count(context, part, count, true);
+            }
+        }
+        map
+    }
+
+    /// Count that `part` followed `context` `n` times. `is_new` is whether `part` never followed `context` before.
+    pub(crate) fn count(&mut self, context: &SentenceContext, part: Part, n: usize, is_new: bool) {
+        match part {
+            Part::Word(word) => {
+                let oldest = match context.parts().first() {
+                    Some(&part) => part,
+                    None => return,
+                };
+                let mut next_context = context.clone();
+                next_context.shift(word);
+                self.previous
+                    .entry(next_context)
+                    .or_default()
+                    .count_part_n(oldest, n);
+            }
+            Part::EndOfLine if is_new => {
+                if let Some(&Part::Word(last)) = context.parts().last() {
+                    // Kept sorted, so the saved bytes don't depend on the order the ends were learned in
+                    let ends = self.ends.entry(last).or_default();
+                    if let Err(index) = ends.binary_search(context) {
+                        ends.insert(index, context.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Remove a single count of `part` following `context`, like `Memory::unlearn` does. `is_gone` is whether `part` no longer follows `context` at all.
+    pub(crate) fn uncount(&mut self, context: &SentenceContext, part: Part, is_gone: bool) {
+        match part {
+            Part::Word(word) => {
+                let oldest = match context.parts().first() {
+                    Some(&part) => part,
+                    None => return,
+                };
+                let mut next_context = context.clone();
+                next_context.shift(word);
+                if let Some(previous) = self.previous.get_mut(&next_context) {
+                    previous.uncount_part(&oldest);
+                    if previous.is_empty() {
+                        self.previous.remove(&next_context);
+                    }
+                }
+            }
+            Part::EndOfLine if is_gone => {
+                if let Some(&Part::Word(last)) = context.parts().last() {
+                    if let Some(ends) = self.ends.get_mut(&last) {
+                        if let Ok(index) = ends.binary_search(context) {
+                            ends.remove(index);
+                        }
+                        if ends.is_empty() {
+                            self.ends.remove(&last);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Restore the totals of every `NextPartList` after deserializing, see `NextPartList::recount_total`
+    pub(crate) fn recount_totals(&mut self) {
+        for next in self.previous.values_mut() {
+            next.recount_total();
+        }
+    }
+
+    /// Iterate over the ids of every word in this map
+    pub(crate) fn word_ids(&self) -> impl Iterator<Item = WordId> + '_ {
+        let previous = self
+            .previous
+            .iter()
+            .flat_map(|(context, next)| context.word_ids().chain(next.word_ids()));
+        let ends = self.ends.iter().flat_map(|(&word, ends)| {
+            std::iter::once(word).chain(ends.iter().flat_map(SentenceContext::word_ids))
+        });
+        previous.chain(ends)
+    }
+
+    /// Estimate the amount of bytes this map has allocated on the heap, see `Memory::estimated_size_bytes`
+    pub(crate) fn heap_size(&self) -> usize {
+        map_heap_size(&self.previous)
+            + self
+                .previous
+                .iter()
+                .map(|(context, next)| context.heap_size() + next.heap_size())
+                .sum::<usize>()
+            + map_heap_size(&self.ends)
+            + self
+                .ends
+                .values()
+                .flatten()
+                .map(|context| std::mem::size_of::<SentenceContext>() + context.heap_size())
+                .sum::<usize>()
+    }
+}
+
+/// An index of the contexts that lead to every context of a `Memory`, used to generate sentences backwards.
+///
+/// Create one with `Memory::reverse_index`. The index borrows the memory it was built from, so the memory can't learn new lines while the index exists.
+#[derive(Debug)]
+pub struct ReverseIndex<'a> {
+    memory: &'a Memory,
+    /// The reverse map, if the memory doesn't keep one itself, see `Memory::set_bidirectional`
+    built: Option<ReverseMap>,
+}
+
+impl<'a> ReverseIndex<'a> {
+    pub(crate) fn new(memory: &'a Memory) -> Self {
+        let built = if memory.config.bidirectional {
+            None
+        } else {
+            Some(ReverseMap::build(&memory.words))
+        };
+        Self { memory, built }
+    }
+
+    /// The reverse map of the memory, either built by this index or kept by the memory
+    fn map(&self) -> &ReverseMap {
+        self.built.as_ref().unwrap_or(&self.memory.reverse)
+    }
+
+    /// Tries to produce a sentence that ends with the given `ending_word`, by generating words backwards until the start of a line. The sentence is returned in the normal reading order.
+    ///
+    /// Returns None if the `ending_word` never ended a line, or if no words could be generated before it.
+    pub fn speak_backward(&self, ending_word: &str) -> Option<String> {
+        self.speak_backward_with_rng(ending_word, &mut rand::thread_rng())
+    }
+
+    /// Tries to produce a sentence that ends with the given `ending_word`, like `ReverseIndex::speak_backward`, but uses the given `rng` to pick the words.
+    pub fn speak_backward_with_rng<R: Rng>(
+        &self,
+        ending_word: &str,
+        rng: &mut R,
+    ) -> Option<String> {
+        let ends = self.map().ends.get(&self.memory.id(ending_word)?)?;
+        let memory = self.memory;
+        let ends = ends.iter().filter_map(|context| {
+            // Lines that ended with banned words before they were banned don't end generated sentences
+            if context.word_ids().any(|word| memory.is_banned(word)) {
+                return None;
+            }
+            let count = memory.words.get(context)?.count(&Part::EndOfLine);
+            Some((context, count))
+        });
+        let end_context = pick_by_count(rng, ends)?;
+
+        // The words of the sentence, from the end to the start
+        let mut result: Vec<&str> = end_context
+            .parts()
+            .iter()
+            .rev()
+            .filter_map(|part| match *part {
+                Part::Word(word) => Some(self.memory.word(word)),
+                _ => None,
+            })
+            .collect();
+        let mut len = result.len() - 1;
+        let mut context = end_context.clone();
+
+        // Keep going until we reach __START__
+        while let Some(Part::Word(_)) | Some(Part::Unknown) = context.parts().first() {
+            let previous = match self.map().previous.get(&context).and_then(|previous| {
+                let allowed: Vec<_> = previous
+                    .iter()
+                    .filter(
+                        |(part, _)| !matches!(**part, Part::Word(word) if memory.is_banned(word)),
+                    )
+                    .map(|(&part, count)| (part, count))
+                    .collect();
+                pick_by_count(rng, allowed.into_iter())
+            }) {
+                Some(part) => part,
+                None => break,
+            };
+            context.unshift(previous);
+            match previous {
+                Part::Word(word) => result.push(self.memory.word(word)),
+                // Words outside of the vocabulary are skipped
+                Part::Unknown => {}
+                _ => break,
+            }
+
+            len += 1;
+            if len >= self.memory.config.max_steps || self.memory.should_break(len, rng) {
+                break;
+            }
+        }
+
+        result.reverse();
+        let (first, rest) = result.split_first()?;
+        self.memory.join(first, rest)
+    }
+}
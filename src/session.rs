@@ -0,0 +1,38 @@
+use crate::Memory;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// A conversation with a `Memory`, which picks words with its own seeded rng. The sentences of a session are reproducible: a new session with the same seed says the same sentences for the same starting words, while sessions with different seeds vary.
+///
+/// Create one with `Memory::session`. The session only borrows the memory, so multiple sessions can talk to the same memory at once.
+#[derive(Debug, Clone)]
+pub struct Session<'a> {
+    memory: &'a Memory,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl<'a> Session<'a> {
+    pub(crate) fn new(memory: &'a Memory, seed: u64) -> Self {
+        Self {
+            memory,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed this session was started with. Save it to restore the session later with `Memory::session`.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The memory this session talks to.
+    pub fn memory(&self) -> &'a Memory {
+        self.memory
+    }
+
+    /// Tries to produce a sentence starting with the given `starting_word`, like `Memory::speak`, with the rng of this session. Every call advances the rng, so the next sentence is different.
+    pub fn speak(&mut self, starting_word: &str) -> Option<String> {
+        self.memory.speak_with_rng(starting_word, &mut self.rng)
+    }
+}
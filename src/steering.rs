@@ -0,0 +1,73 @@
+use crate::words::pick_weighted;
+use crate::{Memory, NextPartList, Part, SentenceContext, WordId};
+use std::collections::{HashMap, VecDeque};
+
+/// The least amount of words every context needs to reach a target word, used to steer generation toward that word. See `Memory::speak_toward`
+#[derive(Debug)]
+pub struct Steering {
+    target: WordId,
+    /// For every context that can reach the target, the amount of words until the target. Contexts ending in the target have a distance of 0
+    distances: HashMap<SentenceContext, usize>,
+}
+
+impl Steering {
+    /// Find the distance of every context of the `memory` to the `target` word. This walks the entire chain.
+    pub fn new(memory: &Memory, target: WordId) -> Self {
+        // Walk the word combinations backwards, starting from the contexts that end in the target
+        let mut previous = HashMap::<SentenceContext, Vec<&SentenceContext>>::new();
+        let mut queue = VecDeque::new();
+        let mut distances = HashMap::new();
+        for (context, next) in &memory.words {
+            if context.parts().last() == Some(&Part::Word(target)) {
+                distances.insert(context.clone(), 0);
+                queue.push_back(context.clone());
+            }
+            for (part, _) in next.iter() {
+                if let Part::Word(word) = *part {
+                    let mut next_context = context.clone();
+                    next_context.shift(word);
+                    previous.entry(next_context).or_default().push(context);
+                }
+            }
+        }
+
+        while let Some(context) = queue.pop_front() {
+            let distance = distances[&context] + 1;
+            for &previous_context in previous.get(&context).into_iter().flatten() {
+                if !distances.contains_key(previous_context) {
+                    distances.insert(previous_context.clone(), distance);
+                    queue.push_back(previous_context.clone());
+                }
+            }
+        }
+
+        Self { target, distances }
+    }
+
+    /// The word generation is steered toward
+    pub fn target(&self) -> WordId {
+        self.target
+    }
+
+    /// Pick a random part out of `next`, the parts that follow `context`. Parts are weighed by their counts, divided by the square of the amount of words they need to reach the target, so parts that reach the target sooner are preferred.
+    ///
+    /// Parts that can't reach the target are never picked. Returns None if no part can reach the target.
+    pub fn pick<'a>(
+        &self,
+        context: &SentenceContext,
+        next: &'a NextPartList,
+        rng: &mut impl rand::Rng,
+    ) -> Option<&'a Part> {
+        let weights = next.iter().filter_map(|(part, count)| {
+            let word = match *part {
+                Part::Word(word) => word,
+                _ => return None,
+            };
+            let mut next_context = context.clone();
+            next_context.shift(word);
+            let steps = *self.distances.get(&next_context)? + 1;
+            Some((part, count as f64 / (steps * steps) as f64))
+        });
+        pick_weighted(rng, weights.collect::<Vec<_>>().into_iter())
+    }
+}
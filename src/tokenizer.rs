@@ -0,0 +1,193 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// The way a line is split into words while learning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub enum Tokenizer {
+    /// Split the line on ascii whitespace. Punctuation stays part of the word it's attached to, so "hello," and "hello" are different words
+    #[default]
+    Whitespace,
+
+    /// Split the line on ascii whitespace, and split leading and trailing punctuation (`.`, `,`, `!` and `?`) into their own words. When speaking, punctuation is attached to the previous word
+    PunctuationAware,
+
+    /// Split the line on any unicode whitespace, like non-breaking spaces. Use `Normalization::UnicodeLower` to also lowercase words with the unicode lowercase rules
+    UnicodeWhitespace,
+
+    /// Split the line like `UnicodeWhitespace`, and split punctuation into their own words like `PunctuationAware`
+    UnicodePunctuationAware,
+}
+
+/// The way words are normalized before they are learned or looked up, so differently written words count as the same word
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub enum Normalization {
+    /// Keep words as they are, so "The" and "the" are different words
+    None,
+
+    /// Lowercase ascii letters only. Other letters keep their casing, so "Über" and "über" are different words
+    #[default]
+    AsciiLower,
+
+    /// Lowercase words with the unicode lowercase rules, so "Über" and "über" are the same word
+    UnicodeLower,
+}
+
+impl Normalization {
+    /// Normalize the given word. Words that are normalized already are borrowed.
+    pub fn apply(self, word: &str) -> Cow<'_, str> {
+        match self {
+            Normalization::None => Cow::Borrowed(word),
+            Normalization::AsciiLower => {
+                if word.bytes().any(|b| b.is_ascii_uppercase()) {
+                    Cow::Owned(word.to_ascii_lowercase())
+                } else {
+                    Cow::Borrowed(word)
+                }
+            }
+            Normalization::UnicodeLower => {
+                let is_lowercase = word.chars().all(|c| {
+                    let mut lowercase = c.to_lowercase();
+                    lowercase.next() == Some(c) && lowercase.next().is_none()
+                });
+                if is_lowercase {
+                    Cow::Borrowed(word)
+                } else {
+                    Cow::Owned(word.to_lowercase())
+                }
+            }
+        }
+    }
+}
+
+/// What happens to words that are longer than the maximum token length, see `Memory::set_max_token_len`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub enum LongTokens {
+    /// Don't learn the word at all
+    #[default]
+    Drop,
+
+    /// Only learn the first characters of the word, up to the maximum token length
+    Truncate,
+}
+
+impl LongTokens {
+    /// Apply this to a word that may be longer than `max_len` characters. Returns None if the word is dropped.
+    pub fn limit(self, word: &str, max_len: usize) -> Option<&str> {
+        match word.char_indices().nth(max_len) {
+            None => Some(word),
+            Some(_) if self == LongTokens::Drop => None,
+            Some((end, _)) => Some(&word[..end]),
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Split the given line into words
+    pub fn tokenize<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        let words: Vec<&str> = if self.is_unicode() {
+            line.split_whitespace().collect()
+        } else {
+            line.split_ascii_whitespace().collect()
+        };
+        if !self.splits_punctuation() {
+            return words;
+        }
+
+        let mut result = Vec::new();
+        for word in words {
+            // split "...word!?" into "...", "word" and "!?"
+            let without_leading = word.trim_start_matches(is_punctuation);
+            let leading = &word[..word.len() - without_leading.len()];
+            let core = without_leading.trim_end_matches(is_punctuation);
+            let trailing = &without_leading[core.len()..];
+            result.extend(
+                [leading, core, trailing]
+                    .iter()
+                    .filter(|part| !part.is_empty()),
+            );
+        }
+        result
+    }
+
+    /// Checks if the given word is a punctuation token that should be attached to the previous word when speaking
+    pub fn is_punctuation(&self, word: &str) -> bool {
+        self.splits_punctuation() && !word.is_empty() && word.chars().all(is_punctuation)
+    }
+
+    pub(crate) fn is_unicode(&self) -> bool {
+        matches!(
+            self,
+            Tokenizer::UnicodeWhitespace | Tokenizer::UnicodePunctuationAware
+        )
+    }
+
+    fn splits_punctuation(&self) -> bool {
+        matches!(
+            self,
+            Tokenizer::PunctuationAware | Tokenizer::UnicodePunctuationAware
+        )
+    }
+}
+
+/// Split `text` into sentences, after every `.`, `!` or `?` that is followed by whitespace. The punctuation stays part of the sentence it ends
+#[cfg(feature = "std")]
+pub(crate) fn sentences(text: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let ends_sentence = matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        if ends_sentence {
+            let end = index + c.len_utf8();
+            result.push(&text[start..end]);
+            start = end;
+        }
+    }
+    result.push(&text[start..]);
+    result
+}
+
+/// Uppercase the first letter of `sentence`, and leave the rest of it as it is
+pub(crate) fn capitalize_first(sentence: &mut String) {
+    if let Some(first) = sentence.chars().next() {
+        let upper: String = first.to_uppercase().collect();
+        sentence.replace_range(..first.len_utf8(), &upper);
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    matches!(c, '.' | ',' | '!' | '?')
+}
+
+/// A predicate that splits lines into words wherever it returns true, see `Memory::with_split`
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub(crate) struct Split(Arc<dyn Fn(char) -> bool + Send + Sync>);
+
+#[cfg(feature = "std")]
+impl Split {
+    pub(crate) fn new(predicate: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+
+    /// Split the given line into words wherever the predicate returns true, leaving out empty words
+    pub(crate) fn tokenize<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        line.split(|c| (self.0)(c))
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for Split {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Split(..)")
+    }
+}
@@ -0,0 +1,42 @@
+//! The version header that is saved next to every chain, so files of another format version can be detected when loading.
+
+use crate::Error;
+use std::io::{Read, Write};
+
+/// The name of the zip entry that contains the version header
+pub const VERSION_ENTRY: &str = "version";
+
+/// The bytes every version header starts with
+const MAGIC: &[u8; 4] = b"MRKV";
+
+/// The version of the saved format. Bump this whenever the layout of a saved chain changes. The settings are saved by name, so adding a setting with a default doesn't change the layout.
+pub const FORMAT_VERSION: u32 = 18;
+
+/// The oldest version of the saved format that can still be loaded
+pub const OLDEST_SUPPORTED_VERSION: u32 = 1;
+
+/// Write the version header of the current format
+pub fn write_header(writer: &mut impl Write) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())
+}
+
+/// Read a version header, and check that it is a supported format. Returns the version of the format.
+pub fn read_header(reader: &mut impl Read) -> Result<u32, Error> {
+    let mut header = [0; 8];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| Error::InvalidVersionHeader)?;
+    let (magic, version) = header.split_at(4);
+    if magic != MAGIC {
+        return Err(Error::InvalidVersionHeader);
+    }
+    let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+    if !(OLDEST_SUPPORTED_VERSION..=FORMAT_VERSION).contains(&version) {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            newest: FORMAT_VERSION,
+        });
+    }
+    Ok(version)
+}
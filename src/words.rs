@@ -1,81 +1,302 @@
+use crate::{Map, Part, WordId};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
 
-/// Helper struct to contain 2 parts
-#[derive(Hash, Debug, Eq, PartialEq, Deserialize, Serialize, Clone)]
-pub struct SentencePartPair {
-    prev: SentencePart,
-    prev_prev: SentencePart,
+/// Helper struct to contain the last `order` parts of a sentence, oldest first
+#[derive(Hash, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize, Clone)]
+#[serde(transparent)]
+pub struct SentenceContext {
+    parts: Vec<Part>,
 }
 
-impl Default for SentencePartPair {
-    fn default() -> Self {
-        Self {
-            prev: SentencePart::StartOfLine,
-            prev_prev: SentencePart::StartOfLine,
-        }
+// Lets maps of contexts be queried with a slice of parts, without allocating a context. The derived `Hash` only hashes `parts`, which hashes the same as the slice.
+impl Borrow<[Part]> for SentenceContext {
+    fn borrow(&self) -> &[Part] {
+        &self.parts
     }
 }
 
-impl SentencePartPair {
-    /// Create a pair with the segments (__START__, s)
-    pub fn with_previous_word(s: impl Into<String>) -> Self {
+impl SentenceContext {
+    /// Create a context of `order` segments at the start of a line: (__START__, ..., __START__)
+    pub fn new(order: usize) -> Self {
         Self {
-            prev: SentencePart::Word(s.into()),
-            prev_prev: SentencePart::StartOfLine,
+            parts: vec![Part::StartOfLine; order],
+        }
+    }
+
+    /// Create a context from the given parts, oldest first
+    pub fn from_parts(parts: Vec<Part>) -> Self {
+        Self { parts }
+    }
+
+    /// Create a context of `order` segments ending in `word`: (__START__, ..., word)
+    pub fn with_previous_word(order: usize, word: WordId) -> Self {
+        let mut context = Self::new(order);
+        context.shift(word);
+        context
+    }
+
+    /// Create a context of `order` segments from the given words, oldest first. If fewer than `order` words are given, the context is padded with __START__. If more are given, only the last `order` words are used
+    pub fn from_words(order: usize, words: impl IntoIterator<Item = WordId>) -> Self {
+        let mut context = Self::new(order);
+        for word in words {
+            context.shift(word);
+        }
+        context
+    }
+
+    /// Walk the word combinations of a line of `words`, like `Memory::learn` counts them: every context together with the part that follows it, from (__START__, ..., first word) to (..., last word) followed by __END__.
+    ///
+    /// Words that are None are not known to the chain, so every combination that contains them is skipped.
+    pub fn for_each_transition(
+        order: usize,
+        words: &[Option<WordId>],
+        mut f: impl FnMut(&SentenceContext, Part),
+    ) {
+        // We split the line into chunks of `order` parts, e.g. for an order of 2:
+        // - __START__ + first word
+        // - first word + second word
+        // - ...
+        // - last_word + __END__
+        let mut context = Self::new(order);
+        // The amount of shifts until the last unknown word is pushed off the context
+        let mut unknown: usize = 0;
+        for word in words {
+            match *word {
+                Some(word) => {
+                    // if the context is a valid word segment, we add the current word to the list of follow-up words.
+                    if unknown == 0 && context.is_valid_sentence() {
+                        f(&context, Part::Word(word));
+                    }
+                    context.shift(word);
+                    unknown = unknown.saturating_sub(1);
+                }
+                None => {
+                    context.shift_part(Part::Unknown);
+                    unknown = order;
+                }
+            }
+        }
+        // this should always be true, unless the caller provides an empty line
+        if unknown == 0 && context.is_valid_sentence() {
+            f(&context, Part::EndOfLine);
         }
     }
 
-    /// Checks to see if this pair is a valid sentence. In effect, it checks if the last SentencePart is a Word
+    /// Checks to see if this context is a valid sentence. In effect, it checks if the last part is a Word
     pub fn is_valid_sentence(&self) -> bool {
-        self.prev.is_word()
+        self.parts.last().is_some_and(Part::is_word)
+    }
+
+    /// Estimate the amount of bytes this context has allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        self.parts.capacity() * std::mem::size_of::<Part>()
     }
 
-    /// Shift the pair, so that (`prev`, `prev_prev`) becomes (`word`, `prev`). The old `prev_prev` gets pushed off
-    pub fn shift(&mut self, new_prev: impl Into<String>) {
-        std::mem::swap(&mut self.prev, &mut self.prev_prev);
-        self.prev = SentencePart::Word(new_prev.into());
+    /// The parts of this context, oldest first
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    /// Iterate over the ids of the words in this context
+    pub fn word_ids(&self) -> impl Iterator<Item = WordId> + '_ {
+        self.parts.iter().filter_map(Part::word_id)
+    }
+
+    /// If this context directly follows the start of a line, returns the word that started the line. In effect, it checks if every part but the last is a StartOfLine
+    pub fn start_word(&self) -> Option<WordId> {
+        let (last, rest) = self.parts.split_last()?;
+        match *last {
+            Part::Word(word) if rest.iter().all(|part| *part == Part::StartOfLine) => Some(word),
+            _ => None,
+        }
+    }
+
+    /// Push `part` onto the start of the context, so that (`a`, ..., `prev`) becomes (`part`, `a`, ...). Returns the part that got pushed off the end
+    pub fn unshift(&mut self, part: Part) -> Option<Part> {
+        self.parts.rotate_right(1);
+        let first = self.parts.first_mut()?;
+        Some(std::mem::replace(first, part))
+    }
+
+    /// Shift the context, like `shift`, but push an arbitrary part instead of a word
+    pub fn shift_part(&mut self, part: Part) {
+        self.parts.rotate_left(1);
+        if let Some(last) = self.parts.last_mut() {
+            *last = part;
+        }
+    }
+
+    /// Create a copy of this context with every part replaced by the result of `f`
+    pub fn map_parts(&self, f: impl FnMut(Part) -> Part) -> Self {
+        Self {
+            parts: self.parts.iter().copied().map(f).collect(),
+        }
+    }
+
+    /// Shift the context, so that (`a`, `b`, ..., `prev`) becomes (`b`, ..., `prev`, `word`). The oldest part gets pushed off
+    pub fn shift(&mut self, word: WordId) {
+        self.shift_part(Part::Word(word));
     }
 }
 
 /// A sentence part, which can either be a StartOfLine, EndOfLine, or an actual word
-#[derive(Hash, Debug, Eq, PartialEq, Deserialize, Serialize, Clone)]
+#[derive(Hash, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize, Clone)]
 pub enum SentencePart {
+    /// The start of a line, before the first word
     StartOfLine,
+    /// The end of a line, after the last word
     EndOfLine,
+    /// A single word
     Word(String),
+    /// A word that was removed from the vocabulary, see `Memory::limit_vocabulary`
+    Unknown,
 }
 
 impl SentencePart {
     /// Check if a given SentencePart is a SentencePart::Word
     pub fn is_word(&self) -> bool {
-        match self {
-            SentencePart::Word(_) => true,
-            _ => false,
-        }
+        matches!(self, SentencePart::Word(_))
     }
 }
 
-/// Wrapper around a `HashMap<SentencePart, usize>`. Used for count how many times a follow-up part occured.
+/// Wrapper around a `HashMap<Part, usize>`. Used for count how many times a follow-up part occured.
 #[derive(Debug, Deserialize, Serialize, Default)]
+#[serde(transparent)]
 pub struct NextPartList {
-    parts: HashMap<SentencePart, usize>,
+    #[serde(with = "crate::pairs")]
+    parts: Map<Part, usize>,
+    /// The sum of all counts, so it doesn't have to be summed up for every picked part. This is not saved, see `NextPartList::recount_total`
+    #[serde(skip)]
+    total: usize,
 }
 
 impl NextPartList {
-    /// Count a part towards this `NextPartList`. If the part does not exist, it will be added.
-    pub fn count_part(&mut self, part: SentencePart) {
-        *self.parts.entry(part).or_insert(0) += 1;
+    /// Count a part `n` times towards this `NextPartList`. If the part does not exist, it will be added.
+    pub fn count_part_n(&mut self, part: Part, n: usize) {
+        let count = self.parts.entry(part).or_insert(0);
+        *count = count.saturating_add(n);
+        self.total = self.total.saturating_add(n);
+    }
+
+    /// Remove a single count of a part from this `NextPartList`. If the count hits zero, the part is removed. Parts that do not exist are ignored.
+    pub fn uncount_part(&mut self, part: &Part) {
+        if let Some(count) = self.parts.get_mut(part) {
+            *count = count.saturating_sub(1);
+            self.total = self.total.saturating_sub(1);
+            if *count == 0 {
+                self.parts.remove(part);
+            }
+        }
+    }
+
+    /// Remove every part that was counted less than `min_count` times. Returns the amount of parts that were removed.
+    pub fn prune(&mut self, min_count: usize) -> usize {
+        let len = self.parts.len();
+        self.parts.retain(|_, count| *count >= min_count);
+        self.recount_total();
+        len - self.parts.len()
+    }
+
+    /// Sum up the counts of all parts again. The total is not saved, so this needs to be called after deserializing a list.
+    pub fn recount_total(&mut self) {
+        self.total = self
+            .parts
+            .values()
+            .fold(0, |total: usize, &count| total.saturating_add(count));
+    }
+
+    /// Estimate the amount of bytes this list has allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        map_heap_size(&self.parts)
+    }
+
+    /// The amount of distinct parts in this list
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Iterate over every part in this list, together with the amount of times it was counted
+    pub fn iter(&self) -> impl Iterator<Item = (&Part, usize)> {
+        self.parts.iter().map(|(part, &count)| (part, count))
+    }
+
+    /// Iterate over the ids of the words that are counted towards this `NextPartList`
+    pub fn word_ids(&self) -> impl Iterator<Item = WordId> + '_ {
+        self.parts.keys().filter_map(Part::word_id)
+    }
+
+    /// Checks if no parts are counted towards this `NextPartList`
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Create a copy of this list with every part replaced by the result of `f`. Parts that end up the same have their counts added together.
+    pub fn map_parts(&self, mut f: impl FnMut(Part) -> Part) -> Self {
+        let mut result = NextPartList {
+            parts: Map::with_capacity_and_hasher(self.parts.len(), Default::default()),
+            total: 0,
+        };
+        for (&part, &count) in &self.parts {
+            result.count_part_n(f(part), count);
+        }
+        result
+    }
+
+    /// Add the counts of `other` to this `NextPartList`. Parts that do not exist yet will be added. Counts saturate at `usize::MAX` instead of overflowing.
+    pub fn merge_counts(&mut self, other: &NextPartList) {
+        for (&part, &count) in &other.parts {
+            self.count_part_n(part, count);
+        }
+    }
+
+    /// The total amount of times any part was counted towards this list
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The amount of times `part` was counted towards this list
+    pub fn count(&self, part: &Part) -> usize {
+        self.parts.get(part).copied().unwrap_or(0)
+    }
+
+    /// The probability that `part` gets picked from this list, between 0.0 and 1.0. Returns 0.0 if the part was never counted
+    pub fn probability(&self, part: &Part) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+        self.count(part) as f64 / total as f64
+    }
+
+    /// Every part of this list with the probability that it gets picked, see `NextPartList::probability`. The probabilities add up to 1.0, give or take rounding errors, unless the list is empty.
+    pub fn distribution(&self) -> Vec<(&Part, f64)> {
+        let total = self.total();
+        if total == 0 {
+            return Vec::new();
+        }
+        self.iter()
+            .map(|(part, count)| (part, count as f64 / total as f64))
+            .collect()
     }
 
     /// Get a random sentence part from this list, weighed towards the part that is mostly used.
     ///
     /// Given a list containing 2 parts, one at 9 usages, and one at 1 usages, this function has a 90% chance to return the first part and a 10% chance to return the second.
-    pub fn get(&self, rng: &mut impl rand::Rng) -> Option<&SentencePart> {
-        if self.parts.is_empty() {
+    pub fn get(&self, rng: &mut impl rand::Rng) -> Option<&Part> {
+        let total = self.total();
+        debug_assert_eq!(
+            total,
+            self.parts
+                .values()
+                .fold(0, |sum: usize, &count| sum.saturating_add(count)),
+            "The cached total of a NextPartList is out of date"
+        );
+        // A corrupt list may only contain counts of 0, and nothing can be picked from it then
+        if total == 0 {
             return None;
         }
-        let total: usize = self.parts.values().sum();
         let mut index = rng.gen_range(0, total);
         // this will always return Some
         self.parts
@@ -87,4 +308,241 @@ impl NextPartList {
             })
             .map(|(part, _)| part)
     }
+
+    /// Get a random sentence part from this list, like `get`, but return a copy that doesn't borrow the list.
+    pub fn get_owned(&self, rng: &mut impl rand::Rng) -> Option<Part> {
+        self.get(rng).copied()
+    }
+
+    /// Get a random sentence part from this list, like `get`, but with every count reweighed to `count ^ (1 / temperature)` first.
+    ///
+    /// A temperature of 1.0 behaves like `get`. Higher temperatures flatten the distribution, so rare parts get picked more often, and lower temperatures sharpen it. A temperature of 0.0 always returns the most used part.
+    pub fn get_with_temperature(
+        &self,
+        rng: &mut impl rand::Rng,
+        temperature: f64,
+    ) -> Option<&Part> {
+        if temperature <= 0.0 {
+            return self.most_used();
+        }
+        if temperature == 1.0 {
+            return self.get(rng);
+        }
+        // Scale the counts to the most used count first, so the weights don't overflow for low temperatures
+        let max = self.parts.values().copied().max()? as f64;
+        let exponent = 1.0 / temperature;
+        pick_weighted(
+            rng,
+            self.parts
+                .iter()
+                .map(|(part, &count)| (part, (count as f64 / max).powf(exponent))),
+        )
+    }
+
+    /// Get a random sentence part from this list, like `get`, but only picks from the `k` most used parts.
+    ///
+    /// With a `k` of 1 this always returns the most used part, and with a `k` of at least the amount of parts in this list this behaves like `get`. Returns None if `k` is 0.
+    pub fn get_top_k(&self, rng: &mut impl rand::Rng, k: usize) -> Option<&Part> {
+        if k >= self.parts.len() {
+            return self.get(rng);
+        }
+        let parts = most_counted(self.iter().collect(), k);
+        pick_weighted(
+            rng,
+            parts.into_iter().map(|(part, count)| (part, count as f64)),
+        )
+    }
+
+    /// Get a random sentence part from this list, like `get`, but only picks from the parts that make up at least `min_prob` of all counts. The counts of the remaining parts are renormalized, so they keep their share of each other.
+    ///
+    /// A `min_prob` of 0.0 behaves like `get`. Returns None if no part is that likely.
+    pub fn get_min_probability(&self, rng: &mut impl rand::Rng, min_prob: f64) -> Option<&Part> {
+        pick_weighted(
+            rng,
+            at_least_probable(self.iter().collect(), min_prob)
+                .into_iter()
+                .map(|(part, count)| (part, count as f64)),
+        )
+    }
+
+    /// Get a random sentence part from this list, weighed by the weight `f` returns for every part and its count, e.g. `|_, count| count as f64` behaves like `get`. This allows custom sampling strategies without changing the chain.
+    ///
+    /// Parts with a weight that isn't positive are never picked. Returns None if no part has a positive weight.
+    pub fn get_weighted_by<F: Fn(&Part, usize) -> f64>(
+        &self,
+        rng: &mut impl rand::Rng,
+        f: F,
+    ) -> Option<&Part> {
+        // `f` may be expensive, so it's only called once for every part
+        let weights: Vec<_> = self
+            .iter()
+            .map(|(part, count)| (part, f(part, count)))
+            .collect();
+        pick_weighted(rng, weights.into_iter())
+    }
+
+    /// Get the part that was counted most often. If multiple parts share the highest count, any one of them is returned.
+    pub fn most_used(&self) -> Option<&Part> {
+        self.parts
+            .iter()
+            .max_by_key(|(_, &count)| count)
+            .map(|(part, _)| part)
+    }
+}
+
+/// Estimate the amount of bytes the table of a `HashMap` has allocated on the heap, not counting the heap allocations of its keys and values
+pub fn map_heap_size<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    // Every slot of the table stores a key, a value, and a control byte
+    map.capacity() * (std::mem::size_of::<(K, V)>() + 1)
+}
+
+/// Keep the `n` items with the highest counts, sorted from the highest to the lowest count. Ties are broken arbitrarily.
+pub fn most_counted<T>(mut items: Vec<(T, usize)>, n: usize) -> Vec<(T, usize)> {
+    if n == 0 {
+        return Vec::new();
+    }
+    if n < items.len() {
+        items.select_nth_unstable_by(n - 1, |(_, a), (_, b)| b.cmp(a));
+        items.truncate(n);
+    }
+    items.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+    items
+}
+
+/// Keep the items whose count makes up at least `min_prob` of the counts of all items
+fn at_least_probable<T>(mut items: Vec<(T, usize)>, min_prob: f64) -> Vec<(T, usize)> {
+    let total = items.iter().map(|(_, count)| count).sum::<usize>() as f64;
+    items.retain(|(_, count)| *count as f64 / total >= min_prob);
+    items
+}
+
+/// Get a random part, weighed by the given weights. Parts with a weight that isn't positive are never picked.
+pub fn pick_weighted<'a>(
+    rng: &mut impl rand::Rng,
+    weights: impl Iterator<Item = (&'a Part, f64)> + Clone,
+) -> Option<&'a Part> {
+    let weights = weights.filter(|(_, weight)| *weight > 0.0);
+    let total: f64 = weights.clone().map(|(_, weight)| weight).sum();
+    if !total.is_finite() || total <= 0.0 {
+        return None;
+    }
+    let mut index = rng.gen_range(0.0, total);
+    let mut last = None;
+    for (part, weight) in weights {
+        if weight > index {
+            return Some(part);
+        }
+        index -= weight;
+        last = Some(part);
+    }
+    // rounding errors can make us run past the last part
+    last
+}
+
+/// The way a part is picked from a `NextPartList`
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Sampler {
+    /// Weighed by the counts of each part, see `NextPartList::get`
+    #[default]
+    Counts,
+    /// Weighed by the reweighed counts of each part, see `NextPartList::get_with_temperature`
+    Temperature(f64),
+    /// Weighed by the counts of the most used parts, see `NextPartList::get_top_k`
+    TopK(usize),
+    /// Weighed by the counts of the parts that make up at least the given share of all counts, see `NextPartList::get_min_probability`
+    MinProbability(f64),
+}
+
+impl Sampler {
+    /// Pick a part from the given list
+    pub(crate) fn sample<'a>(
+        &self,
+        next: &'a NextPartList,
+        rng: &mut impl rand::Rng,
+    ) -> Option<&'a Part> {
+        match *self {
+            Sampler::Counts => next.get(rng),
+            Sampler::Temperature(temperature) => next.get_with_temperature(rng, temperature),
+            Sampler::TopK(k) => next.get_top_k(rng, k),
+            Sampler::MinProbability(min_prob) => next.get_min_probability(rng, min_prob),
+        }
+    }
+
+    /// Pick a part from the given list, like `Sampler::sample`, but with the weight of every `used` word multiplied by `penalty`. If `words_only` is true and the list contains any words, only words are picked.
+    pub(crate) fn sample_penalized<'a>(
+        &self,
+        next: &'a NextPartList,
+        rng: &mut impl rand::Rng,
+        used: &HashSet<WordId>,
+        penalty: f64,
+        words_only: bool,
+    ) -> Option<&'a Part> {
+        let words_only = words_only && next.iter().any(|(part, _)| part.is_word());
+        if !words_only && (penalty == 1.0 || used.is_empty()) {
+            return self.sample(next, rng);
+        }
+        let weights = self
+            .weights(next, words_only)
+            .into_iter()
+            .map(|(part, weight)| match part {
+                Part::Word(word) if used.contains(word) => (part, weight * penalty),
+                _ => (part, weight),
+            });
+        pick_weighted(rng, weights)
+    }
+
+    /// The weight every part of the given list is picked with. If `words_only` is true, every part that isn't a word is left out before weighing
+    fn weights<'a>(&self, next: &'a NextPartList, words_only: bool) -> Vec<(&'a Part, f64)> {
+        let parts: Vec<_> = next
+            .iter()
+            .filter(|(part, _)| !words_only || part.is_word())
+            .collect();
+        let counts = |parts: Vec<(&'a Part, usize)>| {
+            parts
+                .into_iter()
+                .map(|(part, count)| (part, count as f64))
+                .collect()
+        };
+        match *self {
+            Sampler::Counts => counts(parts),
+            Sampler::Temperature(temperature) if temperature <= 0.0 => parts
+                .iter()
+                .max_by_key(|(_, count)| *count)
+                .map(|&(part, _)| (part, 1.0))
+                .into_iter()
+                .collect(),
+            Sampler::Temperature(temperature) => {
+                let max = parts.iter().map(|(_, count)| *count).max().unwrap_or(0) as f64;
+                let exponent = 1.0 / temperature;
+                parts
+                    .into_iter()
+                    .map(|(part, count)| (part, (count as f64 / max).powf(exponent)))
+                    .collect()
+            }
+            Sampler::TopK(k) => counts(most_counted(parts, k)),
+            Sampler::MinProbability(min_prob) => counts(at_least_probable(parts, min_prob)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picking_from_zero_counts_returns_none() {
+        let mut next = NextPartList::default();
+        next.count_part_n(Part::Word(0), 0);
+        next.count_part_n(Part::EndOfLine, 0);
+        let mut rng = rand::thread_rng();
+        assert_eq!(next.get(&mut rng), None);
+        assert_eq!(next.get_owned(&mut rng), None);
+    }
+
+    #[test]
+    fn picking_from_deserialized_zero_counts_returns_none() {
+        let mut next: NextPartList = serde_json::from_str(r#"[["EndOfLine", 0]]"#).unwrap();
+        next.recount_total();
+        assert_eq!(next.get(&mut rand::thread_rng()), None);
+    }
 }